@@ -0,0 +1,190 @@
+use anyhow::{anyhow, bail, Result};
+use regex::Regex;
+use serde_json::Value;
+
+/// A `json:<pointer>` or `regex:<capture group>:<expression>` extractor, it reads values out of
+/// the responses of the peer interface and the http provider
+#[derive(Debug)]
+pub(crate) enum Extractor {
+    Json(String),
+    Regex(usize, Regex),
+}
+
+impl std::str::FromStr for Extractor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parties = s.splitn(2, ':').collect::<Vec<_>>();
+        if parties.len() != 2 {
+            bail!("extractor {} illegal", s)
+        }
+        match parties[0] {
+            "json" => Ok(Extractor::Json(parties[1].to_owned())),
+            "regex" => {
+                let parties = parties[1].splitn(2, ':').collect::<Vec<_>>();
+                if parties.len() != 2 {
+                    bail!(r#"regex extractor format must be "capture_group_number:expression""#)
+                }
+                let index = parties[0]
+                    .parse::<usize>()
+                    .map_err(|err| anyhow!("can't parse capture group index: {}", err))?;
+                let re = Regex::new(parties[1]).map_err(|_| anyhow!("regex illegal {}", parties[1]))?;
+                Ok(Extractor::Regex(index, re))
+            },
+            _ => bail!("unsupported extract method: {}", parties[0]),
+        }
+    }
+}
+
+/// A piece of a response, parsed as json when possible
+pub(crate) struct Fragment {
+    /// What regex extractors match, the response as received or a json string without its quotes
+    text: String,
+    json: Option<Value>,
+}
+
+impl Fragment {
+    pub(crate) fn parse(text: &str) -> Self {
+        let json = serde_json::from_str(text).ok();
+        let text = match &json {
+            Some(Value::String(v)) => v.clone(),
+            _ => text.to_owned(),
+        };
+        Fragment {
+            text,
+            json,
+        }
+    }
+
+    fn from_json(value: Value) -> Self {
+        let text = match &value {
+            Value::String(v) => v.clone(),
+            v => v.to_string(),
+        };
+        Fragment {
+            text,
+            json: Some(value),
+        }
+    }
+
+    pub(crate) fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+impl Extractor {
+    /// Extract a single value
+    pub(crate) fn extract(&self, fragment: &Fragment) -> Result<String> {
+        match self {
+            Extractor::Json(pointer) => {
+                let value = fragment.json.as_ref().ok_or_else(|| anyhow!("response is not json"))?;
+                match value.pointer(pointer) {
+                    Some(Value::String(v)) => Ok(v.clone()),
+                    Some(Value::Null) | None => bail!("can't get {} from response", pointer),
+                    Some(v) => Ok(v.to_string()),
+                }
+            },
+            Extractor::Regex(index, re) => {
+                let caps = re.captures(fragment.text()).ok_or_else(|| anyhow!("can't match"))?;
+                let content = caps
+                    .get(*index)
+                    .ok_or_else(|| anyhow!("can't get capture group {}", index))?;
+                Ok(content.as_str().to_owned())
+            },
+        }
+    }
+
+    /// Split a response into records, a json array or every match of a regex
+    pub(crate) fn split(&self, fragment: Fragment) -> Result<Vec<Fragment>> {
+        match self {
+            Extractor::Json(pointer) => {
+                let value = fragment.json.ok_or_else(|| anyhow!("response is not json"))?;
+                match value.pointer(pointer) {
+                    Some(Value::Array(items)) => Ok(items.iter().cloned().map(Fragment::from_json).collect()),
+                    Some(Value::Null) | None => Ok(vec![]),
+                    Some(_) => bail!("{} of response is not an array", pointer),
+                }
+            },
+            Extractor::Regex(index, re) => Ok(re
+                .captures_iter(fragment.text())
+                .filter_map(|v| v.get(*index))
+                .map(|v| Fragment::parse(v.as_str()))
+                .collect()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract(extractor: &str, text: &str) -> Result<String> {
+        extractor.parse::<Extractor>()?.extract(&Fragment::parse(text))
+    }
+
+    fn split(extractor: &str, text: &str) -> Vec<String> {
+        let extractor = extractor.parse::<Extractor>().unwrap();
+        extractor
+            .split(Fragment::parse(text))
+            .unwrap()
+            .iter()
+            .map(|v| v.text().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn parse_extractor() {
+        assert!(matches!("json:/a/b".parse(), Ok(Extractor::Json(v)) if v == "/a/b"));
+        assert!(matches!("regex:1:a(b)".parse(), Ok(Extractor::Regex(1, _))));
+        assert!("json".parse::<Extractor>().is_err());
+        assert!("regex:a(b)".parse::<Extractor>().is_err());
+        assert!("regex:1:a(b".parse::<Extractor>().is_err());
+        assert!("xpath:/a".parse::<Extractor>().is_err());
+    }
+
+    #[test]
+    fn extract_json() {
+        let text = r#"{"id": 42, "content": "192.0.2.1", "name": null}"#;
+        assert_eq!(extract("json:/content", text).unwrap(), "192.0.2.1");
+        assert_eq!(extract("json:/id", text).unwrap(), "42");
+        assert!(extract("json:/name", text).is_err());
+        assert!(extract("json:/ttl", text).is_err());
+        assert!(extract("json:/content", "192.0.2.1").is_err());
+    }
+
+    #[test]
+    fn extract_regex() {
+        assert_eq!(
+            extract("regex:1:ip=([0-9.]+)", "id=7 ip=192.0.2.1").unwrap(),
+            "192.0.2.1"
+        );
+        assert_eq!(extract("regex:0:[0-9]+", "id=7").unwrap(), "7");
+        // a json string is matched without its quotes
+        assert_eq!(extract("regex:0:.*", r#""192.0.2.1""#).unwrap(), "192.0.2.1");
+        // other json is matched as received
+        assert_eq!(
+            extract(r#"regex:1:"ip": "(.+)""#, r#"{"ip": "192.0.2.1"}"#).unwrap(),
+            "192.0.2.1"
+        );
+        assert!(extract("regex:1:ip=([0-9.]+)", "id=7").is_err());
+        assert!(extract("regex:2:ip=([0-9.]+)", "ip=192.0.2.1").is_err());
+    }
+
+    #[test]
+    fn split_records() {
+        let text = r#"{"records": [{"content": "192.0.2.1"}, {"content": "192.0.2.2"}]}"#;
+        assert_eq!(
+            split("json:/records", text),
+            [r#"{"content":"192.0.2.1"}"#, r#"{"content":"192.0.2.2"}"#]
+        );
+        assert!(split("json:/missing", text).is_empty());
+        assert!("json:/records/0"
+            .parse::<Extractor>()
+            .unwrap()
+            .split(Fragment::parse(text))
+            .is_err());
+
+        let text = "a=192.0.2.1\nb=192.0.2.2\n";
+        assert_eq!(split("regex:1:=(.+)", text), ["192.0.2.1", "192.0.2.2"]);
+    }
+}
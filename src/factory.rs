@@ -7,7 +7,7 @@ use toml::Value;
 
 use crate::interfaces::Interface;
 use crate::notifiers::Notifier;
-use crate::providers::DynProvider;
+use crate::providers::{DynProvider, Provider};
 use crate::{interfaces, notifiers, providers, Shutdown};
 
 macro_rules! from_args_str {
@@ -138,8 +138,18 @@ pub(crate) async fn create_notifier<S: AsRef<str>>(
     Ok(notifier)
 }
 
+/// Box a provider, wrapped to only log the changes when `dry_run` is set
+fn wrap_provider<P: Provider + 'static>(provider: P, dry_run: bool) -> Box<dyn DynProvider> {
+    if dry_run {
+        Box::new(providers::DryRun::new(provider))
+    } else {
+        Box::new(provider)
+    }
+}
+
 pub(crate) async fn create_provider<S: AsRef<str>>(
     shutdown: Arc<Shutdown>,
+    dry_run: bool,
     kind: S,
     args: HashMap<String, Value>,
 ) -> Result<Box<dyn DynProvider>> {
@@ -147,15 +157,290 @@ pub(crate) async fn create_provider<S: AsRef<str>>(
         "cloudflare" => {
             let token = from_args_str!(args, "token");
             let dns = from_args_str!(args, "dns");
-            Box::new(providers::Cloudflare::create(token, dns).await?)
+            wrap_provider(providers::Cloudflare::create(token, dns).await?, dry_run)
         },
         "godaddy" => {
             let api_key = from_args_str!(args, "api_key");
             let secret = from_args_str!(args, "secret");
             let dns = from_args_str!(args, "dns");
-            Box::new(providers::Godaddy::create(api_key, secret, dns).await?)
+            wrap_provider(providers::Godaddy::create(api_key, secret, dns).await?, dry_run)
+        },
+        "rfc2136" => {
+            let server = from_args_str!(args, "server");
+            let dns = from_args_str!(args, "dns");
+            let zone = option_from_args_str!(args, "zone");
+            let tsig = if let Some(key_name) = option_from_args_str!(args, "tsig_key_name") {
+                let secret = from_args_str!(args, "tsig_secret");
+                let algorithm = option_from_args_str!(args, "tsig_algorithm")
+                    .unwrap_or("hmac-sha256")
+                    .parse::<providers::TsigAlgorithm>()?;
+                Some((key_name, secret, algorithm))
+            } else {
+                None
+            };
+            wrap_provider(providers::Rfc2136::create(server, dns, zone, tsig).await?, dry_run)
+        },
+        "route53" => {
+            let hosted_zone_id = from_args_str!(args, "hosted_zone_id");
+            let dns = from_args_str!(args, "dns");
+            let endpoint = option_from_args_str!(args, "endpoint");
+            let region = option_from_args_str!(args, "region");
+            let cred = providers::Route53Credentials::load(
+                option_from_args_str!(args, "access_key_id"),
+                option_from_args_str!(args, "secret_access_key"),
+                option_from_args_str!(args, "session_token"),
+                option_from_args_str!(args, "profile"),
+            )?;
+            wrap_provider(
+                providers::Route53::create(hosted_zone_id, dns, endpoint, region, cred).await?,
+                dry_run,
+            )
+        },
+        "aliyun" => {
+            let dns = from_args_str!(args, "dns");
+            let endpoint = option_from_args_str!(args, "endpoint");
+            let cred = providers::AliyunCredentials {
+                access_key_id: from_args_str!(args, "access_key_id").to_owned(),
+                access_key_secret: from_args_str!(args, "access_key_secret").to_owned(),
+            };
+            wrap_provider(providers::Aliyun::create(dns, endpoint, cred).await?, dry_run)
+        },
+        "dnspod" => {
+            let dns = from_args_str!(args, "dns");
+            let record_line = option_from_args_str!(args, "record_line");
+            let endpoint = option_from_args_str!(args, "endpoint");
+            let cred = providers::DnspodCredentials {
+                secret_id: from_args_str!(args, "secret_id").to_owned(),
+                secret_key: from_args_str!(args, "secret_key").to_owned(),
+            };
+            wrap_provider(
+                providers::Dnspod::create(dns, record_line, endpoint, cred).await?,
+                dry_run,
+            )
+        },
+        "digitalocean" => {
+            let token = from_args_str!(args, "token");
+            let dns = from_args_str!(args, "dns");
+            let domain = option_from_args_str!(args, "domain");
+            let api_url = option_from_args_str!(args, "api_url");
+            wrap_provider(
+                providers::DigitalOcean::create(token, dns, domain, api_url).await?,
+                dry_run,
+            )
+        },
+        "hetzner" => {
+            let token = from_args_str!(args, "token");
+            let dns = from_args_str!(args, "dns");
+            let api_url = option_from_args_str!(args, "api_url");
+            wrap_provider(providers::Hetzner::create(token, dns, api_url).await?, dry_run)
+        },
+        "gandi" => {
+            let token = from_args_str!(args, "token");
+            let dns = from_args_str!(args, "dns");
+            let domain = option_from_args_str!(args, "domain");
+            let api_url = option_from_args_str!(args, "api_url");
+            wrap_provider(providers::Gandi::create(token, dns, domain, api_url).await?, dry_run)
+        },
+        "porkbun" => {
+            let dns = from_args_str!(args, "dns");
+            let domain = option_from_args_str!(args, "domain");
+            let api_url = option_from_args_str!(args, "api_url");
+            let cred = providers::PorkbunCredentials {
+                api_key: from_args_str!(args, "api_key").to_owned(),
+                secret_api_key: from_args_str!(args, "secret_api_key").to_owned(),
+            };
+            wrap_provider(providers::Porkbun::create(dns, domain, api_url, cred).await?, dry_run)
+        },
+        "namecheap" => {
+            let dns = from_args_str!(args, "dns");
+            let api_url = option_from_args_str!(args, "api_url");
+            let dynamic_url = option_from_args_str!(args, "dynamic_url");
+            let mode = if let Some(password) = option_from_args_str!(args, "password") {
+                providers::NamecheapMode::Dynamic {
+                    password: password.to_owned(),
+                }
+            } else {
+                let api_user = from_args_str!(args, "api_user");
+                providers::NamecheapMode::Api {
+                    api_user: api_user.to_owned(),
+                    api_key: from_args_str!(args, "api_key").to_owned(),
+                    username: option_from_args_str!(args, "username").unwrap_or(api_user).to_owned(),
+                    client_ip: from_args_str!(args, "client_ip").to_owned(),
+                }
+            };
+            wrap_provider(
+                providers::Namecheap::create(dns, mode, api_url, dynamic_url).await?,
+                dry_run,
+            )
+        },
+        "dyndns2" => {
+            let url = from_args_str!(args, "url");
+            let dns = from_args_str!(args, "dns");
+            let username = from_args_str!(args, "username");
+            let password = from_args_str!(args, "password");
+            wrap_provider(providers::Dyndns2::create(url, dns, username, password).await?, dry_run)
+        },
+        "duckdns" => {
+            let token = from_args_str!(args, "token");
+            let dns = from_args_str!(args, "dns");
+            let api_url = option_from_args_str!(args, "api_url");
+            wrap_provider(providers::DuckDns::create(token, dns, api_url).await?, dry_run)
+        },
+        "desec" => {
+            let token = from_args_str!(args, "token");
+            let dns = from_args_str!(args, "dns");
+            let domain = option_from_args_str!(args, "domain");
+            let api_url = option_from_args_str!(args, "api_url");
+            wrap_provider(providers::Desec::create(token, dns, domain, api_url).await?, dry_run)
+        },
+        "powerdns" => {
+            let api_url = from_args_str!(args, "api_url");
+            let api_key = from_args_str!(args, "api_key");
+            let dns = from_args_str!(args, "dns");
+            let zone = option_from_args_str!(args, "zone");
+            let server_id = option_from_args_str!(args, "server_id");
+            let notify = option_from_args_bool!(args, "notify").unwrap_or(false);
+            wrap_provider(
+                providers::PowerDns::create(api_url, api_key, dns, zone, server_id, notify).await?,
+                dry_run,
+            )
+        },
+        "linode" => {
+            let token = from_args_str!(args, "token");
+            let dns = from_args_str!(args, "dns");
+            let api_url = option_from_args_str!(args, "api_url");
+            wrap_provider(providers::Linode::create(token, dns, api_url).await?, dry_run)
+        },
+        "vultr" => {
+            let token = from_args_str!(args, "token");
+            let dns = from_args_str!(args, "dns");
+            let api_url = option_from_args_str!(args, "api_url");
+            wrap_provider(providers::Vultr::create(token, dns, api_url).await?, dry_run)
+        },
+        "ovh" => {
+            let dns = from_args_str!(args, "dns");
+            let zone = option_from_args_str!(args, "zone");
+            let endpoint = option_from_args_str!(args, "endpoint");
+            let cred = providers::OvhCredentials {
+                application_key: from_args_str!(args, "application_key").to_owned(),
+                application_secret: from_args_str!(args, "application_secret").to_owned(),
+                consumer_key: from_args_str!(args, "consumer_key").to_owned(),
+            };
+            wrap_provider(providers::Ovh::create(cred, dns, zone, endpoint).await?, dry_run)
+        },
+        "azure" => {
+            let subscription_id = from_args_str!(args, "subscription_id");
+            let resource_group = from_args_str!(args, "resource_group");
+            let dns = from_args_str!(args, "dns");
+            let zone = option_from_args_str!(args, "zone");
+            let token_url = option_from_args_str!(args, "token_url");
+            let arm_url = option_from_args_str!(args, "arm_url");
+            let cred = providers::AzureCredentials {
+                tenant_id: from_args_str!(args, "tenant_id").to_owned(),
+                client_id: from_args_str!(args, "client_id").to_owned(),
+                client_secret: from_args_str!(args, "client_secret").to_owned(),
+            };
+            wrap_provider(
+                providers::Azure::create(cred, subscription_id, resource_group, dns, zone, token_url, arm_url).await?,
+                dry_run,
+            )
+        },
+        "gcloud" => {
+            let key_file = from_args_str!(args, "key_file");
+            let managed_zone = from_args_str!(args, "managed_zone");
+            let dns = from_args_str!(args, "dns");
+            let project = option_from_args_str!(args, "project");
+            let api_url = option_from_args_str!(args, "api_url");
+            let token_url = option_from_args_str!(args, "token_url");
+            wrap_provider(
+                providers::GCloud::create(key_file, managed_zone, dns, project, api_url, token_url).await?,
+                dry_run,
+            )
+        },
+        "namecom" => {
+            let username = from_args_str!(args, "username");
+            let token = from_args_str!(args, "token");
+            let dns = from_args_str!(args, "dns");
+            let domain = option_from_args_str!(args, "domain");
+            let api_url = option_from_args_str!(args, "api_url");
+            wrap_provider(
+                providers::NameCom::create(username, token, dns, domain, api_url).await?,
+                dry_run,
+            )
+        },
+        "namesilo" => {
+            let api_key = from_args_str!(args, "api_key");
+            let dns = from_args_str!(args, "dns");
+            let domain = option_from_args_str!(args, "domain");
+            let api_url = option_from_args_str!(args, "api_url");
+            wrap_provider(
+                providers::NameSilo::create(api_key, dns, domain, api_url).await?,
+                dry_run,
+            )
+        },
+        "dynadot" => {
+            let api_key = from_args_str!(args, "api_key");
+            let dns = from_args_str!(args, "dns");
+            let domain = option_from_args_str!(args, "domain");
+            let api_url = option_from_args_str!(args, "api_url");
+            wrap_provider(
+                providers::Dynadot::create(api_key, dns, domain, api_url).await?,
+                dry_run,
+            )
+        },
+        "zonefile" => {
+            let path = from_args_str!(args, "path");
+            let dns = from_args_str!(args, "dns");
+            let zone = option_from_args_str!(args, "zone");
+            let serial = option_from_args_str!(args, "serial")
+                .unwrap_or("increment")
+                .parse::<providers::SerialMode>()?;
+            let reload_command = option_from_args_str!(args, "reload_command");
+            wrap_provider(
+                providers::ZoneFile::create(path, dns, zone, serial, reload_command).await?,
+                dry_run,
+            )
+        },
+        "hosts" | "dnsmasq" | "unbound" => {
+            let format = match kind.as_ref() {
+                "hosts" => providers::LocalFileFormat::Hosts,
+                "dnsmasq" => providers::LocalFileFormat::Dnsmasq,
+                _ => providers::LocalFileFormat::Unbound,
+            };
+            let path = from_args_str!(args, "path");
+            let dns = from_args_str!(args, "dns");
+            let reload_command = option_from_args_str!(args, "reload_command");
+            wrap_provider(
+                providers::LocalFile::create(format, path, dns, reload_command).await?,
+                dry_run,
+            )
+        },
+        "http" => {
+            let config = Value::Table(args.into_iter().collect()).try_into::<providers::HttpConfig>()?;
+            wrap_provider(providers::Http::create(config).await?, dry_run)
+        },
+        "exec" => {
+            let command = args
+                .get("command")
+                .ok_or(anyhow!("missing command arg"))?
+                .as_array()
+                .ok_or(anyhow!("arg command unknown type"))?
+                .iter()
+                .map(|v| v.as_str().map(|v| v.to_owned()))
+                .collect::<Option<Vec<_>>>()
+                .ok_or(anyhow!("arg command unknown type"))?;
+            let dns = from_args_str!(args, "dns");
+            let long_lived = option_from_args_bool!(args, "long_lived").unwrap_or(false);
+            let timeout = match option_from_args_integer!(args, "timeout") {
+                Some(timeout) if timeout <= 0 => bail!("arg timeout must be positive"),
+                timeout => timeout.map(|v| v as u64),
+            };
+            wrap_provider(
+                providers::Exec::create(command, dns, long_lived, timeout).await?,
+                dry_run,
+            )
         },
-        "fake" => Box::new(providers::Fake::create(shutdown).await?),
+        "fake" => wrap_provider(providers::Fake::create(shutdown).await?, dry_run),
         _ => {
             bail!("the kind of provider '{}' not support", kind.as_ref())
         },
@@ -1,12 +1,11 @@
-use std::collections::HashMap;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
-use anyhow::{anyhow, bail, Result};
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use regex::Regex;
 use reqwest::Client;
 
 use super::Interface;
+use crate::extractor::{Extractor, Fragment};
 use crate::IpType;
 
 pub struct Peer {
@@ -14,8 +13,8 @@ pub struct Peer {
     url_v6: String,
     client_v4: Client,
     client_v6: Client,
-    ipv4_field_path: String,
-    ipv6_field_path: String,
+    ipv4_field_path: Extractor,
+    ipv6_field_path: Extractor,
 }
 
 impl Peer {
@@ -34,8 +33,8 @@ impl Peer {
         Ok(Peer {
             url_v4: url_v4.as_ref().to_owned(),
             url_v6: url_v6.as_ref().to_owned(),
-            ipv4_field_path: ipv4_field_path.as_ref().to_owned(),
-            ipv6_field_path: ipv6_field_path.as_ref().to_owned(),
+            ipv4_field_path: ipv4_field_path.as_ref().parse()?,
+            ipv6_field_path: ipv6_field_path.as_ref().parse()?,
             client_v4,
             client_v6,
         })
@@ -46,50 +45,13 @@ impl Peer {
 impl Interface for Peer {
     async fn get_ip(&self, family: IpType) -> anyhow::Result<Vec<IpAddr>> {
         let (url, client, ip_field_path) = match family {
-            IpType::V4 => (&*self.url_v4, &self.client_v4, &*self.ipv4_field_path),
-            IpType::V6 => (&*self.url_v6, &self.client_v6, &*self.ipv6_field_path),
+            IpType::V4 => (&*self.url_v4, &self.client_v4, &self.ipv4_field_path),
+            IpType::V6 => (&*self.url_v6, &self.client_v6, &self.ipv6_field_path),
         };
-        let parties = ip_field_path.splitn(2, ':').collect::<Vec<_>>();
-        if parties.len() != 2 {
-            bail!("ip field path illegal")
-        }
-        let mut ips = vec![];
-        let result = client.get(url).send().await?;
-        match parties[0] {
-            "regex" => {
-                let parties = parties[1].splitn(2, ':').collect::<Vec<_>>();
-                if parties.len() != 2 {
-                    bail!(r#"regex extractor format must be "capture_group_number:expression""#)
-                }
-                let index = parties[0]
-                    .parse::<usize>()
-                    .map_err(|err| anyhow!("can't parse capture group index: {}", err))?;
-                match Regex::new(parties[1]) {
-                    Ok(re) => {
-                        let result = result.text().await?;
-                        let caps = re.captures(&result).ok_or_else(|| anyhow!("can't match"))?;
-                        let content = caps
-                            .get(index)
-                            .ok_or_else(|| anyhow!("can't get capture group {}", index))?;
-                        ips.push(content.as_str().parse()?);
-                    },
-                    Err(_) => {
-                        bail!("regex illegal {}", parties[1])
-                    },
-                }
-            },
-            "json" => {
-                let result = result.json::<HashMap<String, String>>().await?;
-                let result = path_value::to_value(result)?;
-                let ip = result
-                    .get::<String, _, _>(ip_field_path)?
-                    .ok_or_else(|| anyhow!("can't get ip by peer"))?;
-                ips.push(ip.parse()?);
-            },
-            _ => {
-                bail!("unsupported extract method: {}", parties[0])
-            },
-        };
-        Ok(ips)
+        let result = client.get(url).send().await?.text().await?;
+        let ip = ip_field_path
+            .extract(&Fragment::parse(&result))
+            .map_err(|err| anyhow!("can't get ip by peer: {}", err))?;
+        Ok(vec![ip.parse()?])
     }
 }
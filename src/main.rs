@@ -13,7 +13,7 @@ use std::pin::Pin;
 use std::sync::Arc;
 
 use anyhow::{anyhow, bail, Result};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use factory::{create_interface, create_notifier, create_provider};
 use futures::prelude::*;
 use interfaces::Interface;
@@ -31,15 +31,18 @@ use providers::DynProvider;
 use rand::prelude::*;
 use setting::Setting;
 use shutdown::Shutdown;
+use state::{Ownership, State};
 use tokio::time::{interval_at, sleep, Duration, Instant};
 use tokio::{fs, join, pin, select, signal};
 
+mod extractor;
 mod factory;
 mod interfaces;
 mod notifiers;
 mod providers;
 mod setting;
 mod shutdown;
+mod state;
 mod updater;
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -104,13 +107,25 @@ fn setup_logger(level: log::LevelFilter, log_direction: PathBuf) -> Result<log4r
     Ok(log4rs::init_config(config)?)
 }
 
+fn task_families(family: &str) -> Result<&'static [IpType]> {
+    let families: &[IpType] = match family {
+        "ipv4" => &[IpType::V4],
+        "ipv6" => &[IpType::V6],
+        "all" => &[IpType::V4, IpType::V6],
+        _ => {
+            bail!("unknown family {}", family)
+        },
+    };
+    Ok(families)
+}
+
 async fn run_task(
     families: &[IpType],
-    provider: (Arc<Box<dyn DynProvider>>, u32, bool),
+    provider: (Arc<Box<dyn DynProvider>>, u32, bool, Arc<Ownership>, bool),
     interface: Arc<Box<dyn Interface>>,
     notifiers: Vec<Arc<Option<Box<dyn Notifier>>>>,
 ) -> Result<()> {
-    let (provider, ttl, force) = provider;
+    let (provider, ttl, force, ownership, dry_run) = provider;
     for family in families {
         let target_ips = interface.get_ip(*family).await?;
         let ips_str = target_ips.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",");
@@ -126,11 +141,13 @@ async fn run_task(
             continue;
         }
         info!("got ip(s) from interface: [{}]", ips_str);
-        let update_ips = provider.check_and_update(&target_ips, ttl, force, *family).await?;
+        let update_ips = provider
+            .check_and_update(&target_ips, ttl, force, *family, &ownership)
+            .await?;
         if !update_ips.is_empty() {
             for notifier in notifiers.clone() {
                 if let Some(notifier) = &*notifier {
-                    notifier.send(&update_ips).await?;
+                    notifier.send(&update_ips, dry_run).await?;
                 }
             }
         }
@@ -138,7 +155,7 @@ async fn run_task(
     Ok(())
 }
 
-async fn run(shutdown: Arc<Shutdown>, setting: Setting) -> Result<()> {
+async fn run(shutdown: Arc<Shutdown>, setting: Setting, dry_run: bool) -> Result<()> {
     let base = setting.base;
     debug!("building interfaces");
     let mut interface_map = HashMap::new();
@@ -154,27 +171,24 @@ async fn run(shutdown: Arc<Shutdown>, setting: Setting) -> Result<()> {
         notifier_map.insert(name, Arc::new(notifier));
     }
 
+    debug!("loading state from: {}", &base.state_file);
+    let state = Arc::new(State::load(&base.state_file).await?);
+
     debug!("building providers");
     let mut provider_map = HashMap::new();
     for (name, provider) in setting.providers {
         let force = provider.force;
         let ttl = provider.ttl;
-        let provider = create_provider(shutdown.clone(), provider.kind, provider.args).await?;
-        provider_map.insert(name, (Arc::new(provider), ttl, force));
+        let dry_run = dry_run || provider.dry_run;
+        let ownership = Ownership::new(state.clone(), &name, provider.adopt_existing, dry_run);
+        let provider = create_provider(shutdown.clone(), dry_run, provider.kind, provider.args).await?;
+        provider_map.insert(name, (Arc::new(provider), ttl, force, Arc::new(ownership), dry_run));
     }
 
     let shutdown_for_create_all_task = shutdown.clone();
     let create_task = move |start_delay: Duration, task: &setting::Task| -> Result<_> {
         let shutdown_for_create_all_task = shutdown_for_create_all_task.clone();
-        let family = &*task.family;
-        let families: &[IpType] = match family {
-            "ipv4" => &[IpType::V4],
-            "ipv6" => &[IpType::V6],
-            "all" => &[IpType::V4, IpType::V6],
-            _ => {
-                bail!("unknown family {}", family)
-            },
-        };
+        let families = task_families(&task.family)?;
         let mut notifiers = vec![];
         for notifier in &task.notifiers {
             let notifier = notifier_map
@@ -281,10 +295,85 @@ struct Opts {
     /// Current direction, it will use '.' if not specified
     #[arg(short = 'L', long)]
     log_direction: Option<PathBuf>,
+    /// Only log the changes to the records, for every provider
+    #[arg(long)]
+    dry_run: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the changes every task would make to the records, without touching the providers
+    Plan,
+}
+
+/// Print the change set of every task and family, returns whether all of them could be planned
+async fn plan(setting: Setting) -> Result<bool> {
+    let shutdown = Arc::new(Shutdown::new());
+    let state = Arc::new(State::load(&setting.base.state_file).await?);
+    let mut tasks = setting.tasks.iter().collect::<Vec<_>>();
+    tasks.sort_by_key(|(name, _)| *name);
+    let mut succeed = true;
+    for (name, task) in tasks {
+        let families = task_families(&task.family)?;
+        let interface = setting
+            .interfaces
+            .get(&task.interface)
+            .ok_or_else(|| anyhow!("can't find interface define"))?;
+        let interface = create_interface(&interface.kind, interface.args.clone()).await?;
+        let provider_setting = setting
+            .providers
+            .get(&task.provider)
+            .ok_or_else(|| anyhow!("can't find provider define"))?;
+        // wrapped in a dry run so planning can never change a record
+        let provider = create_provider(
+            shutdown.clone(),
+            true,
+            &provider_setting.kind,
+            provider_setting.args.clone(),
+        )
+        .await?;
+        let ownership = Ownership::new(state.clone(), &task.provider, provider_setting.adopt_existing, true);
+        for family in families {
+            println!("task {} (provider {}, {}):", name, task.provider, family);
+            let result = async {
+                let target_ips = interface.get_ip(*family).await?;
+                provider
+                    .plan(
+                        &target_ips,
+                        provider_setting.ttl,
+                        provider_setting.force,
+                        *family,
+                        &ownership,
+                    )
+                    .await
+            }
+            .await;
+            match result {
+                Ok(change_set) => print!("{}", change_set),
+                Err(err) => {
+                    println!("  error: {}", err);
+                    succeed = false;
+                },
+            }
+        }
+    }
+    Ok(succeed)
 }
 
 #[tokio::main]
-async fn real_main(config_file: String, log_level: log::LevelFilter, log_direction: PathBuf) {
+async fn plan_main(config_file: String, log_level: log::LevelFilter, log_direction: PathBuf) -> Result<bool> {
+    setup_logger(log_level, log_direction)?;
+    let setting_contents = fs::read_to_string(&config_file)
+        .await
+        .map_err(|err| anyhow!("can't read config: {}", err))?;
+    let setting: Setting = toml::from_str(&setting_contents).map_err(|err| anyhow!("can't parse config: {}", err))?;
+    plan(setting).await
+}
+
+#[tokio::main]
+async fn real_main(config_file: String, log_level: log::LevelFilter, log_direction: PathBuf, dry_run: bool) {
     // setup logger
     setup_logger(log_level, log_direction).expect("can't setup logger");
 
@@ -317,7 +406,7 @@ async fn real_main(config_file: String, log_level: log::LevelFilter, log_directi
 
         loop {
             // prepare main logic
-            let run_task = run(shutdown.clone(), setting.clone());
+            let run_task = run(shutdown.clone(), setting.clone(), dry_run);
             pin!(run_task);
 
             let reload_sig = async move {
@@ -440,6 +529,18 @@ fn main() {
 
     let log_direction = opts.log_direction.unwrap_or_else(|| current_direction.clone());
 
+    if let Some(Command::Plan) = opts.command {
+        match plan_main(opts.config, log_level, log_direction) {
+            Ok(true) => {},
+            Ok(false) => std::process::exit(1),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            },
+        }
+        return;
+    }
+
     #[cfg(target_family = "unix")]
     {
         use daemonize::Daemonize;
@@ -459,7 +560,7 @@ fn main() {
             daemonize = daemonize.working_directory(current_direction);
             match daemonize.start() {
                 Ok(_) => {
-                    real_main(opts.config, log_level, log_direction);
+                    real_main(opts.config, log_level, log_direction, opts.dry_run);
                 },
                 Err(err) => {
                     error!("can't start daemonize: {}", err);
@@ -467,13 +568,13 @@ fn main() {
             }
         } else {
             info!("starting");
-            real_main(opts.config, log_level, log_direction);
+            real_main(opts.config, log_level, log_direction, opts.dry_run);
         }
     }
 
     #[cfg(not(target_family = "unix"))]
     {
         info!("starting");
-        real_main(opts.config, log_level, log_direction);
+        real_main(opts.config, log_level, log_direction, opts.dry_run);
     }
 }
@@ -63,7 +63,7 @@ impl Email {
     }
 }
 
-fn build_email(new_ips: &[IpAddr]) -> String {
+fn build_email(new_ips: &[IpAddr], dry_run: bool) -> String {
     // Create the html we want to send.
     let html = html! {
         head {
@@ -132,6 +132,12 @@ fn build_email(new_ips: &[IpAddr]) -> String {
                     display: block;
                 }
 
+                .dry-run {
+                    margin: 20px 20px 0 20px;
+                    color: #2a3439;
+                    font-size: 15px;
+                }
+
                 .ip-item {
                     color: #2a3439;
                     font-size: 19px;
@@ -147,6 +153,9 @@ fn build_email(new_ips: &[IpAddr]) -> String {
                     span class="title-text" { "DDNS-RS" }
                 }
             }
+            @if dry_run {
+                p class="dry-run" { "Dry run, these IPs would be written but nothing was changed" }
+            }
             ol class="ip-box" {
                 @for ip in new_ips.iter() {
                     li class="ip-item" {
@@ -159,7 +168,7 @@ fn build_email(new_ips: &[IpAddr]) -> String {
     html.into_string()
 }
 
-fn build_email_plaintext(new_ips: &[IpAddr]) -> String {
+fn build_email_plaintext(new_ips: &[IpAddr], dry_run: bool) -> String {
     let new_ips_str = new_ips
         .iter()
         .map(|v| format!("\t{}\n", v))
@@ -171,27 +180,37 @@ fn build_email_plaintext(new_ips: &[IpAddr]) -> String {
 ─┴┘─┴┘┘└┘└─┘   ┴└─└─┘
 DNS record updater
 "#;
-    format!("{}New IP List:\n{}", logo, new_ips_str)
+    let title = if dry_run {
+        "Would Change IP List (dry run)"
+    } else {
+        "New IP List"
+    };
+    format!("{}{}:\n{}", logo, title, new_ips_str)
 }
 
 #[async_trait]
 impl Notifier for Email {
-    async fn send(&self, new_ips: &[IpAddr]) -> Result<()> {
+    async fn send(&self, new_ips: &[IpAddr], dry_run: bool) -> Result<()> {
+        let subject = if dry_run {
+            format!("[Dry run] {}", self.subject)
+        } else {
+            self.subject.clone()
+        };
         let email = Message::builder()
             .from(self.from.parse().unwrap())
             .to(self.to.parse().unwrap())
-            .subject(&self.subject)
+            .subject(subject)
             .multipart(
                 MultiPart::alternative()
                     .singlepart(
                         SinglePart::builder()
                             .header(header::ContentType::TEXT_PLAIN)
-                            .body(build_email_plaintext(new_ips)),
+                            .body(build_email_plaintext(new_ips, dry_run)),
                     )
                     .singlepart(
                         SinglePart::builder()
                             .header(header::ContentType::TEXT_HTML)
-                            .body(build_email(new_ips)),
+                            .body(build_email(new_ips, dry_run)),
                     ),
             )
             .unwrap();
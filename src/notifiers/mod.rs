@@ -10,5 +10,6 @@ mod webhook;
 
 #[async_trait]
 pub trait Notifier: Send + Sync {
-    async fn send(&self, new_ips: &[IpAddr]) -> Result<()>;
+    /// `dry_run` is set when the records were not changed, `new_ips` are the addresses that would be written
+    async fn send(&self, new_ips: &[IpAddr], dry_run: bool) -> Result<()>;
 }
@@ -36,13 +36,14 @@ impl Webhook {
 
 #[async_trait]
 impl Notifier for Webhook {
-    async fn send(&self, new_ips: &[IpAddr]) -> anyhow::Result<()> {
+    async fn send(&self, new_ips: &[IpAddr], dry_run: bool) -> anyhow::Result<()> {
         let url = &self.url;
         let ipv4_list = new_ips.iter().filter(|v| v.is_ipv4()).collect::<Vec<_>>();
         let ipv6_list = new_ips.iter().filter(|v| v.is_ipv6()).collect::<Vec<_>>();
         let json = vec![json!({
             "ipv4_list": ipv4_list,
             "ipv6_list": ipv6_list,
+            "dry_run": dry_run,
         })];
 
         self.client
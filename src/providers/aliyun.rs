@@ -0,0 +1,221 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use sha1::Sha1;
+
+use super::{percent_encode, record_type_from_ip, relative_name, zone_of, Provider};
+use crate::IpType;
+
+const DEFAULT_ENDPOINT: &str = "https://alidns.aliyuncs.com";
+const API_VERSION: &str = "2015-01-09";
+
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub id: String,
+    pub ip: IpAddr,
+    pub ttl: u32,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+pub struct Credentials {
+    pub access_key_id: String,
+    pub access_key_secret: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorResponse {
+    #[serde(rename = "Code")]
+    code: String,
+    #[serde(rename = "Message")]
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct DescribeSubDomainRecordsResponse {
+    #[serde(rename = "DomainRecords")]
+    domain_records: DomainRecords,
+}
+
+#[derive(Deserialize, Debug)]
+struct DomainRecords {
+    #[serde(rename = "Record", default)]
+    records: Vec<Record>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Record {
+    #[serde(rename = "RecordId")]
+    id: String,
+    #[serde(rename = "Type")]
+    kind: String,
+    #[serde(rename = "Value")]
+    value: String,
+    #[serde(rename = "TTL")]
+    ttl: u32,
+}
+
+pub struct Aliyun {
+    dns: String,
+    domain: String,
+    name: String,
+    endpoint: String,
+    client: Client,
+    cred: Credentials,
+}
+
+impl Aliyun {
+    pub async fn create<D: AsRef<str>>(dns: D, endpoint: Option<&str>, cred: Credentials) -> Result<Self> {
+        let dns = dns.as_ref().trim_end_matches('.').to_owned();
+
+        let domain = zone_of(&dns, None)?;
+        let name = relative_name(&dns, &domain)?;
+        let name = if name.is_empty() { "@".to_owned() } else { name };
+
+        Ok(Aliyun {
+            dns,
+            domain,
+            name,
+            endpoint: endpoint.unwrap_or(DEFAULT_ENDPOINT).trim_end_matches('/').to_owned(),
+            client: Client::new(),
+            cred,
+        })
+    }
+
+    async fn request<T: DeserializeOwned>(&self, action: &str, params: &[(&str, &str)]) -> Result<T> {
+        let timestamp = Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
+        let nonce = rand::random::<u64>().to_string();
+        let mut params = params.to_vec();
+        params.extend_from_slice(&[
+            ("Action", action),
+            ("Format", "JSON"),
+            ("Version", API_VERSION),
+            ("AccessKeyId", &self.cred.access_key_id),
+            ("SignatureMethod", "HMAC-SHA1"),
+            ("SignatureVersion", "1.0"),
+            ("SignatureNonce", &nonce),
+            ("Timestamp", &timestamp),
+        ]);
+        params.sort();
+        let query = params
+            .iter()
+            .map(|(k, v)| format!("{}={}", percent_encode(k), percent_encode(v)))
+            .collect::<Vec<_>>()
+            .join("&");
+        let string_to_sign = format!("GET&{}&{}", percent_encode("/"), percent_encode(&query));
+        let signature = Hmac::<Sha1>::new_from_slice(format!("{}&", self.cred.access_key_secret).as_bytes())
+            .map_err(|_| anyhow!("invalid access key secret"))?
+            .chain_update(string_to_sign.as_bytes())
+            .finalize()
+            .into_bytes();
+        let url = format!(
+            "{}/?Signature={}&{}",
+            self.endpoint,
+            percent_encode(&BASE64.encode(signature)),
+            query
+        );
+
+        let response = self.client.get(url).send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            match serde_json::from_str::<ErrorResponse>(&text) {
+                Ok(err) => bail!("aliyun error {}: {}", err.code, err.message),
+                Err(_) => bail!("aliyun error {}: {}", status, text),
+            }
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+#[async_trait]
+impl Provider for Aliyun {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        let kind = match family {
+            IpType::V4 => "A",
+            IpType::V6 => "AAAA",
+        };
+        let response: DescribeSubDomainRecordsResponse = self
+            .request(
+                "DescribeSubDomainRecords",
+                &[
+                    ("SubDomain", &self.dns),
+                    ("DomainName", &self.domain),
+                    ("Type", kind),
+                    ("PageSize", "500"),
+                ],
+            )
+            .await?;
+        let mut records = vec![];
+        for record in response.domain_records.records {
+            if record.kind != kind {
+                continue;
+            }
+            records.push(DNSRecord {
+                id: record.id,
+                ip: record.value.parse()?,
+                ttl: record.ttl,
+            });
+        }
+        Ok(records)
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        let _: serde_json::Value = self
+            .request(
+                "AddDomainRecord",
+                &[
+                    ("DomainName", &self.domain),
+                    ("RR", &self.name),
+                    ("Type", record_type_from_ip(ip)),
+                    ("Value", &ip.to_string()),
+                    ("TTL", &ttl.to_string()),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        let _: serde_json::Value = self
+            .request(
+                "UpdateDomainRecord",
+                &[
+                    ("RecordId", &record.id),
+                    ("RR", &self.name),
+                    ("Type", record_type_from_ip(ip)),
+                    ("Value", &ip.to_string()),
+                    ("TTL", &record.ttl.to_string()),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        let _: serde_json::Value = self.request("DeleteDomainRecord", &[("RecordId", &record.id)]).await?;
+        Ok(())
+    }
+}
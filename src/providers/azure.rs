@@ -0,0 +1,307 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use log::debug;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use super::{
+    create_in_record_set,
+    delete_from_record_set,
+    record_type_from_family,
+    relative_name,
+    update_in_record_set,
+    zone_of,
+    Provider,
+};
+use crate::IpType;
+
+const DEFAULT_AUTHORITY_URL: &str = "https://login.microsoftonline.com";
+const DEFAULT_ARM_URL: &str = "https://management.azure.com";
+const API_VERSION: &str = "2018-05-01";
+/// Refresh the token a bit before it actually expires
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub ip: IpAddr,
+    pub ttl: u32,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.ip, self.ttl)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorResponse {
+    error: ErrorDetail,
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorDetail {
+    code: String,
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RecordSet {
+    properties: RecordSetProperties,
+}
+
+#[derive(Deserialize, Debug)]
+struct RecordSetProperties {
+    #[serde(rename = "TTL")]
+    ttl: u32,
+    #[serde(rename = "ARecords", default)]
+    a_records: Vec<ARecord>,
+    #[serde(rename = "AAAARecords", default)]
+    aaaa_records: Vec<AaaaRecord>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ARecord {
+    #[serde(rename = "ipv4Address")]
+    ipv4_address: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct AaaaRecord {
+    #[serde(rename = "ipv6Address")]
+    ipv6_address: String,
+}
+
+pub struct Credentials {
+    pub tenant_id: String,
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+struct Token {
+    access_token: String,
+    expires_at: Instant,
+}
+
+pub struct Azure {
+    zone_url: String,
+    name: String,
+    token_url: String,
+    scope: String,
+    credentials: Credentials,
+    client: Client,
+    token: Mutex<Option<Token>>,
+}
+
+impl Azure {
+    pub async fn create<S: AsRef<str>, G: AsRef<str>, D: AsRef<str>>(
+        credentials: Credentials,
+        subscription_id: S,
+        resource_group: G,
+        dns: D,
+        zone: Option<&str>,
+        token_url: Option<&str>,
+        arm_url: Option<&str>,
+    ) -> Result<Self> {
+        let dns = dns.as_ref().trim_end_matches('.').to_owned();
+
+        let zone = zone_of(&dns, zone)?;
+        let name = relative_name(&dns, &zone)?;
+        let name = if name.is_empty() { "@".to_owned() } else { name };
+
+        let arm_url = arm_url.unwrap_or(DEFAULT_ARM_URL).trim_end_matches('/');
+        let zone_url = format!(
+            "{}/subscriptions/{}/resourceGroups/{}/providers/Microsoft.Network/dnsZones/{}",
+            arm_url,
+            subscription_id.as_ref(),
+            resource_group.as_ref(),
+            zone
+        );
+        let token_url = match token_url {
+            Some(token_url) => token_url.to_owned(),
+            None => format!("{}/{}/oauth2/v2.0/token", DEFAULT_AUTHORITY_URL, credentials.tenant_id),
+        };
+
+        Ok(Azure {
+            zone_url,
+            name,
+            token_url,
+            scope: format!("{}/.default", arm_url),
+            credentials,
+            client: Client::new(),
+            token: Mutex::new(None),
+        })
+    }
+
+    /// Get the cached access token, requesting a new one when it's about to expire
+    async fn access_token(&self) -> Result<String> {
+        let mut token = self.token.lock().await;
+        if let Some(token) = &*token {
+            if token.expires_at > Instant::now() {
+                return Ok(token.access_token.clone());
+            }
+        }
+
+        debug!("requesting azure access token");
+        let response = self
+            .client
+            .post(&self.token_url)
+            .form(&[
+                ("grant_type", "client_credentials"),
+                ("client_id", &self.credentials.client_id),
+                ("client_secret", &self.credentials.client_secret),
+                ("scope", &self.scope),
+            ])
+            .send()
+            .await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            match serde_json::from_str::<TokenErrorResponse>(&text) {
+                Ok(err) => bail!(
+                    "azure token error {}: {} {}",
+                    status,
+                    err.error,
+                    err.error_description.unwrap_or_default()
+                ),
+                Err(_) => bail!("azure token error {}: {}", status, text),
+            }
+        }
+        let response = serde_json::from_str::<TokenResponse>(&text)?;
+        let lifetime = Duration::from_secs(response.expires_in);
+        *token = Some(Token {
+            access_token: response.access_token.clone(),
+            expires_at: Instant::now() + lifetime.saturating_sub(TOKEN_REFRESH_MARGIN.min(lifetime / 2)),
+        });
+        Ok(response.access_token)
+    }
+
+    fn url(&self, kind: &str) -> String {
+        format!("{}/{}/{}?api-version={}", self.zone_url, kind, self.name, API_VERSION)
+    }
+
+    /// `Ok(None)` means the record set doesn't exist
+    async fn send(&self, builder: RequestBuilder) -> Result<Option<String>> {
+        let response = builder.bearer_auth(self.access_token().await?).send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if status == StatusCode::UNAUTHORIZED {
+            // the token may have been revoked, get a new one next time
+            self.token.lock().await.take();
+        }
+        if !status.is_success() {
+            match serde_json::from_str::<ErrorResponse>(&text) {
+                Ok(err) => bail!("azure error {}: {} {}", status, err.error.code, err.error.message),
+                Err(_) => bail!("azure error {}: {}", status, text),
+            }
+        }
+        Ok(Some(text))
+    }
+
+    async fn get_record_set(&self, kind: &str) -> Result<Vec<DNSRecord>> {
+        let text = match self.send(self.client.get(self.url(kind))).await? {
+            Some(text) => text,
+            None => return Ok(vec![]),
+        };
+        let rrset = serde_json::from_str::<RecordSet>(&text)?;
+        let ttl = rrset.properties.ttl;
+        let mut records = vec![];
+        let values = rrset
+            .properties
+            .a_records
+            .into_iter()
+            .map(|v| v.ipv4_address)
+            .chain(rrset.properties.aaaa_records.into_iter().map(|v| v.ipv6_address));
+        for value in values {
+            records.push(DNSRecord {
+                ip: value.parse()?,
+                ttl,
+            });
+        }
+        Ok(records)
+    }
+
+    async fn put_record_set(&self, kind: &str, ttl: u32, ips: &[IpAddr]) -> Result<()> {
+        if ips.is_empty() {
+            self.send(self.client.delete(self.url(kind))).await?;
+        } else {
+            let mut properties = json!({ "TTL": ttl });
+            match kind {
+                "A" => {
+                    properties["ARecords"] = ips.iter().map(|v| json!({"ipv4Address": v.to_string()})).collect();
+                },
+                _ => {
+                    properties["AAAARecords"] = ips.iter().map(|v| json!({"ipv6Address": v.to_string()})).collect();
+                },
+            }
+            let json = json!({ "properties": properties });
+            self.send(self.client.put(self.url(kind)).json(&json))
+                .await?
+                .ok_or_else(|| anyhow!("azure error: zone {} not found", self.zone_url))?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Provider for Azure {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        self.get_record_set(record_type_from_family(family)).await
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        create_in_record_set(self, ip, ttl).await
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        update_in_record_set(self, &record.ip, ip, record.ttl).await
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        delete_from_record_set(self, &record.ip, record.ttl).await
+    }
+
+    fn supports_replace(&self) -> bool {
+        true
+    }
+
+    async fn replace_dns_records(
+        &self,
+        family: IpType,
+        records: &[Self::DNSRecord],
+        ips: &[IpAddr],
+        ttl: u32,
+    ) -> Result<()> {
+        let ttl = records.first().map(|v| v.ttl).unwrap_or(ttl);
+        self.put_record_set(record_type_from_family(family), ttl, ips).await
+    }
+}
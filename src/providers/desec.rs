@@ -0,0 +1,233 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use log::warn;
+use reqwest::{header, Client, Method, StatusCode};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::sync::Mutex;
+use tokio::time::{sleep, Instant};
+
+use super::{
+    create_in_record_set,
+    delete_from_record_set,
+    record_type_from_family,
+    relative_name,
+    update_in_record_set,
+    zone_of,
+    Provider,
+};
+use crate::IpType;
+
+const DEFAULT_API_URL: &str = "https://desec.io/api/v1";
+/// deSEC allows 2 rrset writes per second per domain
+const WRITE_INTERVAL: Duration = Duration::from_millis(500);
+const MAX_RETRIES: usize = 3;
+/// Give up instead of blocking the task when deSEC asks to wait longer than this
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub ip: IpAddr,
+    pub ttl: u32,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.ip, self.ttl)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorResponse {
+    detail: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct RecordSet {
+    ttl: u32,
+    #[serde(default)]
+    records: Vec<String>,
+}
+
+pub struct Desec {
+    domain: String,
+    subname: String,
+    api_url: String,
+    token: String,
+    client: Client,
+    last_write: Mutex<Option<Instant>>,
+}
+
+impl Desec {
+    pub async fn create<T: AsRef<str>, D: AsRef<str>>(
+        token: T,
+        dns: D,
+        domain: Option<&str>,
+        api_url: Option<&str>,
+    ) -> Result<Self> {
+        let dns = dns.as_ref().trim_end_matches('.').to_owned();
+
+        let domain = zone_of(&dns, domain)?;
+        let subname = relative_name(&dns, &domain)?;
+
+        Ok(Desec {
+            domain,
+            subname,
+            api_url: api_url.unwrap_or(DEFAULT_API_URL).trim_end_matches('/').to_owned(),
+            token: token.as_ref().to_owned(),
+            client: Client::new(),
+            last_write: Mutex::new(None),
+        })
+    }
+
+    fn collection_url(&self) -> String {
+        format!("{}/domains/{}/rrsets/", self.api_url, self.domain)
+    }
+
+    fn url(&self, kind: &str) -> String {
+        let subname = if self.subname.is_empty() { "@" } else { &self.subname };
+        format!("{}{}/{}/", self.collection_url(), subname, kind)
+    }
+
+    /// Wait until the write rate limit allows another request
+    async fn throttle(&self) {
+        let mut last_write = self.last_write.lock().await;
+        if let Some(last) = *last_write {
+            let elapsed = last.elapsed();
+            if elapsed < WRITE_INTERVAL {
+                sleep(WRITE_INTERVAL - elapsed).await;
+            }
+        }
+        *last_write = Some(Instant::now());
+    }
+
+    /// `Ok(None)` means the record set doesn't exist
+    async fn send(&self, method: Method, url: &str, body: Option<&Value>) -> Result<Option<String>> {
+        let mut retries = 0;
+        loop {
+            if method != Method::GET {
+                self.throttle().await;
+            }
+            let mut builder = self
+                .client
+                .request(method.clone(), url)
+                .header(header::AUTHORIZATION, format!("Token {}", self.token));
+            if let Some(body) = body {
+                builder = builder.json(body);
+            }
+            let response = builder.send().await?;
+            let status = response.status();
+            if status == StatusCode::TOO_MANY_REQUESTS && retries < MAX_RETRIES {
+                let retry_after = response
+                    .headers()
+                    .get(header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.parse().ok())
+                    .map(Duration::from_secs)
+                    .unwrap_or(WRITE_INTERVAL);
+                if retry_after <= MAX_RETRY_AFTER {
+                    warn!("desec rate limited, retry after {:?}", retry_after);
+                    retries += 1;
+                    sleep(retry_after).await;
+                    continue;
+                }
+            }
+            let text = response.text().await?;
+            if status == StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+            if !status.is_success() {
+                match serde_json::from_str::<ErrorResponse>(&text) {
+                    Ok(err) => bail!("desec error {}: {}", status, err.detail),
+                    Err(_) => bail!("desec error {}: {}", status, text),
+                }
+            }
+            return Ok(Some(text));
+        }
+    }
+
+    async fn get_record_set(&self, kind: &str) -> Result<Vec<DNSRecord>> {
+        let text = match self.send(Method::GET, &self.url(kind), None).await? {
+            Some(text) => text,
+            None => return Ok(vec![]),
+        };
+        let rrset = serde_json::from_str::<RecordSet>(&text)?;
+        let mut records = vec![];
+        for value in rrset.records {
+            records.push(DNSRecord {
+                ip: value.parse()?,
+                ttl: rrset.ttl,
+            });
+        }
+        Ok(records)
+    }
+
+    /// Patch the whole record set, an empty `ips` deletes it
+    async fn patch_record_set(&self, kind: &str, ttl: u32, ips: &[IpAddr]) -> Result<()> {
+        let records = ips.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        let json = json!({
+            "records": records,
+            "ttl": ttl,
+        });
+        let patched = self.send(Method::PATCH, &self.url(kind), Some(&json)).await?;
+        if patched.is_none() && !ips.is_empty() {
+            let json = json!({
+                "subname": self.subname,
+                "type": kind,
+                "records": records,
+                "ttl": ttl,
+            });
+            self.send(Method::POST, &self.collection_url(), Some(&json))
+                .await?
+                .ok_or_else(|| anyhow!("desec error: domain {} not found", self.domain))?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Provider for Desec {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        self.get_record_set(record_type_from_family(family)).await
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        create_in_record_set(self, ip, ttl).await
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        update_in_record_set(self, &record.ip, ip, record.ttl).await
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        delete_from_record_set(self, &record.ip, record.ttl).await
+    }
+
+    fn supports_replace(&self) -> bool {
+        true
+    }
+
+    async fn replace_dns_records(
+        &self,
+        family: IpType,
+        records: &[Self::DNSRecord],
+        ips: &[IpAddr],
+        ttl: u32,
+    ) -> Result<()> {
+        let ttl = records.first().map(|v| v.ttl).unwrap_or(ttl);
+        self.patch_record_set(record_type_from_family(family), ttl, ips).await
+    }
+}
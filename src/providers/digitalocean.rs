@@ -0,0 +1,191 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{record_type_from_ip, relative_name, zone_of, Provider};
+use crate::IpType;
+
+const DEFAULT_API_URL: &str = "https://api.digitalocean.com";
+const PER_PAGE: u32 = 200;
+
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub id: u64,
+    pub ip: IpAddr,
+    pub ttl: u32,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorResponse {
+    id: String,
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct ListRecordsResponse {
+    #[serde(default)]
+    domain_records: Vec<Record>,
+    #[serde(default)]
+    links: Links,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct Links {
+    #[serde(default)]
+    pages: Pages,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct Pages {
+    next: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Record {
+    id: u64,
+    #[serde(rename = "type")]
+    kind: String,
+    data: String,
+    ttl: u32,
+}
+
+pub struct DigitalOcean {
+    dns: String,
+    domain: String,
+    name: String,
+    api_url: String,
+    token: String,
+    client: Client,
+}
+
+impl DigitalOcean {
+    pub async fn create<T: AsRef<str>, D: AsRef<str>>(
+        token: T,
+        dns: D,
+        domain: Option<&str>,
+        api_url: Option<&str>,
+    ) -> Result<Self> {
+        let dns = dns.as_ref().trim_end_matches('.').to_owned();
+
+        let domain = zone_of(&dns, domain)?;
+        let name = relative_name(&dns, &domain)?;
+        let name = if name.is_empty() { "@".to_owned() } else { name };
+
+        Ok(DigitalOcean {
+            dns,
+            domain,
+            name,
+            api_url: api_url.unwrap_or(DEFAULT_API_URL).trim_end_matches('/').to_owned(),
+            token: token.as_ref().to_owned(),
+            client: Client::new(),
+        })
+    }
+
+    async fn send<T: DeserializeOwned>(&self, builder: RequestBuilder) -> Result<T> {
+        let response = builder.bearer_auth(&self.token).send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            match serde_json::from_str::<ErrorResponse>(&text) {
+                Ok(err) => bail!("digitalocean error {}: {}", err.id, err.message),
+                Err(_) => bail!("digitalocean error {}: {}", status, text),
+            }
+        }
+        if text.is_empty() {
+            return Ok(serde_json::from_value(Value::Null)?);
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+#[async_trait]
+impl Provider for DigitalOcean {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        let kind = match family {
+            IpType::V4 => "A",
+            IpType::V6 => "AAAA",
+        };
+        let url = format!("{}/v2/domains/{}/records", self.api_url, self.domain);
+        let mut records = vec![];
+        let mut current_page = 1;
+        loop {
+            let response: ListRecordsResponse = self
+                .send(self.client.get(&url).query(&[
+                    ("type", kind),
+                    ("name", &self.dns),
+                    ("page", &current_page.to_string()),
+                    ("per_page", &PER_PAGE.to_string()),
+                ]))
+                .await?;
+
+            for record in response.domain_records {
+                if record.kind != kind {
+                    continue;
+                }
+                records.push(DNSRecord {
+                    id: record.id,
+                    ip: record.data.parse()?,
+                    ttl: record.ttl,
+                });
+            }
+
+            if response.links.pages.next.is_none() {
+                break;
+            }
+            current_page += 1;
+        }
+        Ok(records)
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        let url = format!("{}/v2/domains/{}/records", self.api_url, self.domain);
+        let _: Value = self
+            .send(self.client.post(url).json(&json!({
+                "type": record_type_from_ip(ip),
+                "name": self.name,
+                "data": ip.to_string(),
+                "ttl": ttl,
+            })))
+            .await?;
+        Ok(())
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        let url = format!("{}/v2/domains/{}/records/{}", self.api_url, self.domain, record.id);
+        let _: Value = self
+            .send(self.client.patch(url).json(&json!({
+                "type": record_type_from_ip(ip),
+                "data": ip.to_string(),
+                "ttl": record.ttl,
+            })))
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        let url = format!("{}/v2/domains/{}/records/{}", self.api_url, self.domain, record.id);
+        let _: Value = self.send(self.client.delete(url)).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,312 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use reqwest::{Client, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{hmac_sha256, record_type_from_ip, relative_name, sha256_hex, zone_of, Provider};
+use crate::IpType;
+
+const DEFAULT_ENDPOINT: &str = "https://dnspod.tencentcloudapi.com";
+const DEFAULT_RECORD_LINE: &str = "默认";
+const API_VERSION: &str = "2021-03-23";
+const SERVICE: &str = "dnspod";
+
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub id: u64,
+    pub ip: IpAddr,
+    pub ttl: u32,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+pub struct Credentials {
+    pub secret_id: String,
+    pub secret_key: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Response {
+    #[serde(rename = "Response")]
+    response: ResponseBody,
+}
+
+#[derive(Deserialize, Debug)]
+struct ResponseBody {
+    #[serde(rename = "Error")]
+    error: Option<ErrorDetail>,
+    /// The rest of the body, only parsed as the result once there is no error
+    #[serde(flatten)]
+    result: Value,
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorDetail {
+    #[serde(rename = "Code")]
+    code: String,
+    #[serde(rename = "Message")]
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct DescribeRecordListResponse {
+    #[serde(rename = "RecordList", default)]
+    records: Vec<Record>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Record {
+    #[serde(rename = "RecordId")]
+    id: u64,
+    #[serde(rename = "Type")]
+    kind: String,
+    #[serde(rename = "Value")]
+    value: String,
+    #[serde(rename = "TTL")]
+    ttl: u32,
+}
+
+pub struct Dnspod {
+    domain: String,
+    name: String,
+    record_line: String,
+    endpoint: Url,
+    client: Client,
+    cred: Credentials,
+}
+
+impl Dnspod {
+    pub async fn create<D: AsRef<str>>(
+        dns: D,
+        record_line: Option<&str>,
+        endpoint: Option<&str>,
+        cred: Credentials,
+    ) -> Result<Self> {
+        let dns = dns.as_ref().trim_end_matches('.');
+
+        let domain = zone_of(dns, None)?;
+        let name = relative_name(dns, &domain)?;
+        let name = if name.is_empty() { "@".to_owned() } else { name };
+
+        Ok(Dnspod {
+            domain,
+            name,
+            record_line: record_line.unwrap_or(DEFAULT_RECORD_LINE).to_owned(),
+            endpoint: Url::parse(endpoint.unwrap_or(DEFAULT_ENDPOINT))?,
+            client: Client::new(),
+            cred,
+        })
+    }
+
+    /// Call an API 3.0 action, `Ok(None)` means `DescribeRecordList` failed with `ResourceNotFound.NoDataOfRecord`,
+    /// which is how dnspod reports an empty list
+    async fn request<T: DeserializeOwned>(&self, action: &str, payload: Value) -> Result<Option<T>> {
+        let payload = payload.to_string();
+        let host = match self.endpoint.port() {
+            Some(port) => format!("{}:{}", self.endpoint.host_str().unwrap_or_default(), port),
+            None => self.endpoint.host_str().unwrap_or_default().to_owned(),
+        };
+        let content_type = "application/json; charset=utf-8";
+        let timestamp = Utc::now().timestamp();
+        let date = Utc
+            .timestamp_opt(timestamp, 0)
+            .single()
+            .ok_or_else(|| anyhow!("invalid timestamp"))?
+            .format("%Y-%m-%d")
+            .to_string();
+
+        let signed_headers = "content-type;host;x-tc-action";
+        let canonical_request = format!(
+            "POST\n/\n\ncontent-type:{}\nhost:{}\nx-tc-action:{}\n\n{}\n{}",
+            content_type,
+            host,
+            action.to_ascii_lowercase(),
+            signed_headers,
+            sha256_hex(payload.as_bytes())
+        );
+        let scope = format!("{}/{}/tc3_request", date, SERVICE);
+        let string_to_sign = format!(
+            "TC3-HMAC-SHA256\n{}\n{}\n{}",
+            timestamp,
+            scope,
+            sha256_hex(canonical_request.as_bytes())
+        );
+        let key = hmac_sha256(format!("TC3{}", self.cred.secret_key).as_bytes(), date.as_bytes());
+        let key = hmac_sha256(&key, SERVICE.as_bytes());
+        let key = hmac_sha256(&key, b"tc3_request");
+        let signature = hex::encode(hmac_sha256(&key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "TC3-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.cred.secret_id, scope, signed_headers, signature
+        );
+
+        let response = self
+            .client
+            .post(self.endpoint.clone())
+            .header(reqwest::header::AUTHORIZATION, authorization)
+            .header(reqwest::header::CONTENT_TYPE, content_type)
+            .header(reqwest::header::HOST, host)
+            .header("X-TC-Action", action)
+            .header("X-TC-Timestamp", timestamp.to_string())
+            .header("X-TC-Version", API_VERSION)
+            .body(payload)
+            .send()
+            .await?;
+        let status = response.status();
+        let text = response.text().await?;
+        parse_response(action, status, &text)
+    }
+}
+
+fn parse_response<T: DeserializeOwned>(action: &str, status: StatusCode, text: &str) -> Result<Option<T>> {
+    let response = match serde_json::from_str::<Response>(text) {
+        Ok(v) => v.response,
+        Err(_) => bail!("dnspod error {}: {}", status, text),
+    };
+    match response.error {
+        Some(err) if action == "DescribeRecordList" && err.code == "ResourceNotFound.NoDataOfRecord" => Ok(None),
+        Some(err) => bail!("dnspod error {}: {}", err.code, err.message),
+        // a result that doesn't parse is an error, it would otherwise read as an empty record list
+        None => serde_json::from_value(response.result)
+            .map(Some)
+            .map_err(|err| anyhow!("can't parse dnspod {} response: {}", action, err)),
+    }
+}
+
+#[async_trait]
+impl Provider for Dnspod {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        let kind = match family {
+            IpType::V4 => "A",
+            IpType::V6 => "AAAA",
+        };
+        let response: Option<DescribeRecordListResponse> = self
+            .request(
+                "DescribeRecordList",
+                json!({
+                    "Domain": self.domain,
+                    "Subdomain": self.name,
+                    "RecordType": kind,
+                    "RecordLine": self.record_line,
+                    "Limit": 3000,
+                }),
+            )
+            .await?;
+        let mut records = vec![];
+        for record in response.map(|v| v.records).unwrap_or_default() {
+            if record.kind != kind {
+                continue;
+            }
+            records.push(DNSRecord {
+                id: record.id,
+                ip: record.value.parse()?,
+                ttl: record.ttl,
+            });
+        }
+        Ok(records)
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        let _: Option<Value> = self
+            .request(
+                "CreateRecord",
+                json!({
+                    "Domain": self.domain,
+                    "SubDomain": self.name,
+                    "RecordType": record_type_from_ip(ip),
+                    "RecordLine": self.record_line,
+                    "Value": ip.to_string(),
+                    "TTL": ttl,
+                }),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        let _: Option<Value> = self
+            .request(
+                "ModifyRecord",
+                json!({
+                    "Domain": self.domain,
+                    "SubDomain": self.name,
+                    "RecordType": record_type_from_ip(ip),
+                    "RecordLine": self.record_line,
+                    "Value": ip.to_string(),
+                    "RecordId": record.id,
+                    "TTL": record.ttl,
+                }),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        let _: Option<Value> = self
+            .request(
+                "DeleteRecord",
+                json!({
+                    "Domain": self.domain,
+                    "RecordId": record.id,
+                }),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_record_list() {
+        let text = r#"{"Response": {"RecordList": [{"RecordId": 1, "Type": "A", "Value": "192.0.2.1", "TTL": 600}],
+            "RequestId": "x"}}"#;
+        let response: Option<DescribeRecordListResponse> =
+            parse_response("DescribeRecordList", StatusCode::OK, text).unwrap();
+        let records = response.unwrap().records;
+        assert_eq!(records.len(), 1);
+        assert_eq!(
+            (records[0].id, records[0].value.as_str(), records[0].ttl),
+            (1, "192.0.2.1", 600)
+        );
+    }
+
+    #[test]
+    fn parse_empty_record_list() {
+        let text = r#"{"Response": {"Error": {"Code": "ResourceNotFound.NoDataOfRecord", "Message": "no data"}}}"#;
+        let response: Option<DescribeRecordListResponse> =
+            parse_response("DescribeRecordList", StatusCode::OK, text).unwrap();
+        assert!(response.is_none());
+        // only the record list treats it as empty
+        assert!(parse_response::<Value>("ModifyRecord", StatusCode::OK, text).is_err());
+    }
+
+    #[test]
+    fn parse_illegal_record_list() {
+        let text = r#"{"Response": {"RecordList": [{"RecordId": "1", "Type": "A"}], "RequestId": "x"}}"#;
+        let response = parse_response::<DescribeRecordListResponse>("DescribeRecordList", StatusCode::OK, text);
+        assert!(response.is_err());
+        assert!(parse_response::<Value>("ModifyRecord", StatusCode::BAD_GATEWAY, "<html>").is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use std::net::IpAddr;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use log::info;
+
+use super::Provider;
+use crate::IpType;
+
+/// Wraps any provider so the records are read but never changed, the changes are only logged
+pub struct DryRun<P> {
+    inner: P,
+}
+
+impl<P: Provider> DryRun<P> {
+    pub fn new(inner: P) -> Self {
+        DryRun {
+            inner,
+        }
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for DryRun<P> {
+    type DNSRecord = P::DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        self.inner.get_dns_record(family).await
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        info!("dry run: would create dns record {} with ttl {}", ip, ttl);
+        Ok(())
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        info!("dry run: would update dns record {} to {}", record.as_ref(), ip);
+        Ok(())
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        info!("dry run: would delete dns record {}", record.as_ref());
+        Ok(())
+    }
+
+    fn supports_replace(&self) -> bool {
+        self.inner.supports_replace()
+    }
+
+    async fn replace_dns_records(
+        &self,
+        family: IpType,
+        _records: &[Self::DNSRecord],
+        ips: &[IpAddr],
+        _ttl: u32,
+    ) -> Result<()> {
+        let ips_str = ips.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",");
+        info!("dry run: would replace {} dns record(s) with [{}]", family, ips_str);
+        Ok(())
+    }
+
+    async fn commit_changes(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::providers::tests::{ips, Mock};
+    use crate::providers::DynProvider;
+    use crate::state::tests::state_path;
+    use crate::state::{Ownership, State};
+
+    #[tokio::test]
+    async fn changes_nothing() {
+        let dry_run = DryRun::new(Mock::new(&["192.0.2.1", "192.0.2.2"], false));
+        let records = dry_run.get_dns_record(IpType::V4).await.unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(!dry_run.supports_replace());
+
+        let ip = ips(&["192.0.2.3"])[0];
+        dry_run.create_dns_record(&ip, 600).await.unwrap();
+        dry_run.update_dns_record(&records[0], &ip).await.unwrap();
+        dry_run.delete_dns_record(&records[1]).await.unwrap();
+        dry_run
+            .replace_dns_records(IpType::V4, &records, &[ip], 600)
+            .await
+            .unwrap();
+        dry_run.commit_changes().await.unwrap();
+        assert!(dry_run.inner.calls().is_empty());
+        assert_eq!(dry_run.inner.ips(), ["192.0.2.1", "192.0.2.2"]);
+
+        let dry_run = DryRun::new(Mock::new(&[], true));
+        assert!(dry_run.supports_replace());
+    }
+
+    #[tokio::test]
+    async fn keeps_state() {
+        let path = state_path("dry-run");
+        let state = Arc::new(State::load(&path).await.unwrap());
+        let ownership = Ownership::new(state, "p1", false, true);
+        let dry_run = DryRun::new(Mock::new(&["192.0.2.1"], false));
+        let changed = dry_run
+            .check_and_update(&ips(&["192.0.2.3"]), 600, false, IpType::V4, &ownership)
+            .await
+            .unwrap();
+        // the seeded record is planned to be updated, but neither it nor the state are touched
+        assert_eq!(changed, ips(&["192.0.2.3"]));
+        assert!(dry_run.inner.calls().is_empty());
+        assert_eq!(dry_run.inner.ips(), ["192.0.2.1"]);
+        assert_eq!(ownership.owned(IpType::V4).await, None);
+        assert!(!path.exists());
+    }
+}
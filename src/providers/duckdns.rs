@@ -0,0 +1,245 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use log::{debug, warn};
+use reqwest::Client;
+use tokio::sync::Mutex;
+
+use super::{create_in_record_set, delete_from_record_set, lookup_dns_record, update_in_record_set, Provider};
+use crate::IpType;
+
+const DEFAULT_API_URL: &str = "https://www.duckdns.org/update";
+const DOMAIN_SUFFIX: &str = ".duckdns.org";
+/// DuckDNS has no TTL, the record set helpers are given this one
+const NO_TTL: u32 = 0;
+
+/// DuckDNS has no record id, every record is just the IP of a family
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub ip: IpAddr,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.ip)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+#[derive(Default)]
+struct State {
+    ipv4: Option<Option<IpAddr>>,
+    ipv6: Option<Option<IpAddr>>,
+}
+
+pub struct DuckDns {
+    dns: String,
+    subdomain: String,
+    token: String,
+    api_url: String,
+    client: Client,
+    /// The IPs last set, `Some(None)` means the family has been cleared
+    state: Mutex<State>,
+}
+
+impl DuckDns {
+    pub async fn create<T: AsRef<str>, D: AsRef<str>>(token: T, dns: D, api_url: Option<&str>) -> Result<Self> {
+        let dns = dns.as_ref().trim_end_matches('.');
+        let subdomain = dns.trim_end_matches(DOMAIN_SUFFIX);
+        // only the label right before duckdns.org is accepted by the api
+        let subdomain = subdomain.rsplit('.').next().unwrap_or(subdomain).to_owned();
+        Ok(DuckDns {
+            dns: format!("{}{}", subdomain, DOMAIN_SUFFIX),
+            subdomain,
+            token: token.as_ref().to_owned(),
+            api_url: api_url.unwrap_or(DEFAULT_API_URL).to_owned(),
+            client: Client::new(),
+            state: Mutex::new(State::default()),
+        })
+    }
+
+    async fn current_ip(&self, family: IpType) -> Result<Option<IpAddr>> {
+        let cached = {
+            let state = self.state.lock().await;
+            match family {
+                IpType::V4 => state.ipv4,
+                IpType::V6 => state.ipv6,
+            }
+        };
+        match cached {
+            Some(ip) => Ok(ip),
+            None => Ok(lookup_dns_record(&self.dns, family).await?.into_iter().next()),
+        }
+    }
+
+    async fn send(&self, params: &[(&str, String)]) -> Result<()> {
+        let mut query = vec![("domains", self.subdomain.clone()), ("token", self.token.clone())];
+        query.extend_from_slice(params);
+        let text = self
+            .client
+            .get(&self.api_url)
+            .query(&query)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        debug!("duckdns response: {}", text.trim());
+        if !text.trim().starts_with("OK") {
+            bail!("duckdns error: {}", text.trim());
+        }
+        Ok(())
+    }
+
+    /// Set the IP of `family` together with the known IP of the other family in one call
+    async fn set_ip(&self, family: IpType, ip: Option<IpAddr>) -> Result<()> {
+        let (ipv4, ipv6) = match family {
+            IpType::V4 => (ip, self.current_ip(IpType::V6).await?),
+            IpType::V6 => (self.current_ip(IpType::V4).await?, ip),
+        };
+        if ip.is_none() {
+            // clear removes both families, the other one is set back right after
+            self.send(&[("clear", "true".to_owned())]).await?;
+        }
+        let mut params = vec![];
+        match (ipv4, ipv6) {
+            (Some(ip), _) => params.push(("ip", ip.to_string())),
+            // without an ip duckdns sets the ipv4 of the caller, the ipv6 is sent in its place to keep it empty
+            (None, Some(ip)) => params.push(("ip", ip.to_string())),
+            (None, None) => {},
+        }
+        if let Some(ip) = ipv6 {
+            params.push(("ipv6", ip.to_string()));
+        }
+        if !params.is_empty() {
+            self.send(&params).await?;
+        }
+        let mut state = self.state.lock().await;
+        state.ipv4 = Some(ipv4);
+        state.ipv6 = Some(ipv6);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Provider for DuckDns {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        Ok(self
+            .current_ip(family)
+            .await?
+            .map(|ip| DNSRecord {
+                ip,
+            })
+            .into_iter()
+            .collect())
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        create_in_record_set(self, ip, ttl).await
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        update_in_record_set(self, &record.ip, ip, NO_TTL).await
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        delete_from_record_set(self, &record.ip, NO_TTL).await
+    }
+
+    fn supports_replace(&self) -> bool {
+        true
+    }
+
+    async fn replace_dns_records(
+        &self,
+        family: IpType,
+        _records: &[Self::DNSRecord],
+        ips: &[IpAddr],
+        _ttl: u32,
+    ) -> Result<()> {
+        if ips.len() > 1 {
+            warn!("duckdns only keeps one ip per family, using {}", ips[0]);
+        }
+        self.set_ip(family, ips.first().copied()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::tests::{ips, MockServer};
+
+    async fn duckdns(server: &MockServer, ipv4: Option<&str>, ipv6: Option<&str>) -> DuckDns {
+        let provider = DuckDns::create("token", "home.duckdns.org", Some(&format!("{}/update", server.url)))
+            .await
+            .unwrap();
+        {
+            let mut state = provider.state.lock().await;
+            state.ipv4 = Some(ipv4.map(|v| v.parse().unwrap()));
+            state.ipv6 = Some(ipv6.map(|v| v.parse().unwrap()));
+        }
+        provider
+    }
+
+    fn targets(server: &MockServer) -> Vec<String> {
+        server.requests().into_iter().map(|(request, _)| request).collect()
+    }
+
+    #[tokio::test]
+    async fn update_both_families() {
+        let server = MockServer::start(|_, _| (200, "OK".to_owned())).await;
+        let provider = duckdns(&server, Some("192.0.2.1"), Some("2001:db8::1")).await;
+        let record = provider.get_dns_record(IpType::V4).await.unwrap().remove(0);
+        provider
+            .update_dns_record(&record, &ips(&["192.0.2.2"])[0])
+            .await
+            .unwrap();
+        assert_eq!(
+            targets(&server),
+            ["GET /update?domains=home&token=token&ip=192.0.2.2&ipv6=2001%3Adb8%3A%3A1"]
+        );
+        assert_eq!(
+            provider.current_ip(IpType::V4).await.unwrap(),
+            Some(ips(&["192.0.2.2"])[0])
+        );
+    }
+
+    #[tokio::test]
+    async fn keep_ipv4_empty() {
+        let server = MockServer::start(|_, _| (200, "OK".to_owned())).await;
+        let provider = duckdns(&server, Some("192.0.2.1"), None).await;
+        let record = provider.get_dns_record(IpType::V4).await.unwrap().remove(0);
+        provider.delete_dns_record(&record).await.unwrap();
+        provider.create_dns_record(&ips(&["2001:db8::1"])[0], 60).await.unwrap();
+        // an update without ip would bring the ipv4 of the caller back
+        assert_eq!(
+            targets(&server),
+            [
+                "GET /update?domains=home&token=token&clear=true",
+                "GET /update?domains=home&token=token&ip=2001%3Adb8%3A%3A1&ipv6=2001%3Adb8%3A%3A1",
+            ]
+        );
+        assert!(provider.get_dns_record(IpType::V4).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn update_error() {
+        let server = MockServer::start(|_, _| (200, "KO".to_owned())).await;
+        let provider = duckdns(&server, Some("192.0.2.1"), None).await;
+        assert!(provider.create_dns_record(&ips(&["192.0.2.2"])[0], 60).await.is_err());
+        assert_eq!(
+            provider.current_ip(IpType::V4).await.unwrap(),
+            Some(ips(&["192.0.2.1"])[0])
+        );
+    }
+}
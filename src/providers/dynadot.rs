@@ -0,0 +1,289 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use log::debug;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{
+    create_in_record_set,
+    delete_from_record_set,
+    record_type_from_family,
+    relative_name,
+    update_in_record_set,
+    zone_of,
+    Provider,
+};
+use crate::IpType;
+
+const DEFAULT_API_URL: &str = "https://api.dynadot.com/api3.json";
+/// The TTL used when the domain has none yet
+const DEFAULT_TTL: u32 = 300;
+
+/// Dynadot has no record id, a record is identified by its host, type and value
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub ip: IpAddr,
+    /// Shared by every record of the domain
+    pub ttl: u32,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.ip)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct GetDns {
+    #[serde(rename = "NameServerSettings")]
+    settings: NameServerSettings,
+}
+
+#[derive(Deserialize, Debug)]
+struct NameServerSettings {
+    #[serde(rename = "Type", default)]
+    kind: String,
+    #[serde(rename = "MainDomains", default)]
+    main_domains: Vec<Entry>,
+    #[serde(rename = "SubDomains", default)]
+    sub_domains: Vec<Entry>,
+    #[serde(rename = "TTL")]
+    ttl: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct Entry {
+    #[serde(rename = "Subhost")]
+    subhost: Option<String>,
+    #[serde(rename = "RecordType")]
+    kind: String,
+    #[serde(rename = "Value")]
+    value: String,
+    /// The distance of MX records
+    #[serde(rename = "Value2")]
+    value2: Option<String>,
+}
+
+impl Entry {
+    fn host(&self) -> &str {
+        self.subhost.as_deref().unwrap_or("")
+    }
+}
+
+pub struct Dynadot {
+    domain: String,
+    host: String,
+    api_url: String,
+    api_key: String,
+    client: Client,
+}
+
+impl Dynadot {
+    pub async fn create<K: AsRef<str>, D: AsRef<str>>(
+        api_key: K,
+        dns: D,
+        domain: Option<&str>,
+        api_url: Option<&str>,
+    ) -> Result<Self> {
+        let dns = dns.as_ref().trim_end_matches('.').to_owned();
+
+        let domain = zone_of(&dns, domain)?;
+
+        Ok(Dynadot {
+            host: relative_name(&dns, &domain)?,
+            domain,
+            api_url: api_url.unwrap_or(DEFAULT_API_URL).to_owned(),
+            api_key: api_key.as_ref().to_owned(),
+            client: Client::new(),
+        })
+    }
+
+    /// Call a command, returning the body of its `<Command>Response` envelope
+    async fn call(&self, command: &str, params: &[(String, String)]) -> Result<Value> {
+        let mut query = vec![
+            ("key".to_owned(), self.api_key.clone()),
+            ("command".to_owned(), command.to_owned()),
+            ("domain".to_owned(), self.domain.clone()),
+        ];
+        query.extend_from_slice(params);
+        // the api key is in the url, so don't let reqwest put it into the error
+        let response = self
+            .client
+            .get(&self.api_url)
+            .query(&query)
+            .send()
+            .await
+            .map_err(|err| err.without_url())?;
+        let status = response.status();
+        let text = response.text().await.map_err(|err| err.without_url())?;
+        if !status.is_success() {
+            bail!("dynadot error {}: {}", status, text);
+        }
+        let json = serde_json::from_str::<Value>(&text)
+            .map_err(|err| anyhow!("dynadot error: unexpected response {}: {}", err, text))?;
+        let body = json
+            .as_object()
+            .and_then(|v| v.values().next())
+            .ok_or_else(|| anyhow!("dynadot error: unexpected response {}", text))?;
+        // the code is a number or a string depending on the command
+        let code = match &body["ResponseCode"] {
+            Value::String(v) => v.clone(),
+            v => v.to_string(),
+        };
+        if code != "0" {
+            let message = body["Error"].as_str().unwrap_or(&text);
+            bail!("dynadot error {}: {}", code, message);
+        }
+        Ok(body.clone())
+    }
+
+    async fn get_settings(&self) -> Result<NameServerSettings> {
+        let body = self.call("get_dns", &[]).await?;
+        let settings = serde_json::from_value::<GetDns>(body["GetDns"].clone())?.settings;
+        if settings.kind != "Dynadot DNS" {
+            bail!(
+                "dynadot error: {} doesn't use Dynadot DNS but {}",
+                self.domain,
+                settings.kind
+            );
+        }
+        Ok(settings)
+    }
+
+    fn is_ours(&self, entry: &Entry, kind: &str) -> bool {
+        entry.host().eq_ignore_ascii_case(&self.host) && entry.kind.eq_ignore_ascii_case(kind)
+    }
+
+    async fn get_records(&self, kind: &str) -> Result<Vec<DNSRecord>> {
+        let settings = self.get_settings().await?;
+        let ttl = match &settings.ttl {
+            Some(ttl) => ttl
+                .parse()
+                .map_err(|err| anyhow!("dynadot ttl {} illegal: {}", ttl, err))?,
+            None => DEFAULT_TTL,
+        };
+        let mut records = vec![];
+        for entry in settings.main_domains.iter().chain(&settings.sub_domains) {
+            if self.is_ours(entry, kind) {
+                records.push(DNSRecord {
+                    ip: entry.value.parse()?,
+                    ttl,
+                });
+            }
+        }
+        Ok(records)
+    }
+
+    /// Write back every record of the domain, replacing only the records of `kind` on our host
+    async fn set_records(&self, kind: &str, ips: &[IpAddr], ttl: u32) -> Result<()> {
+        let settings = self.get_settings().await?;
+        let mut main_domains = vec![];
+        let mut sub_domains = vec![];
+        for entry in settings.main_domains.into_iter().chain(settings.sub_domains) {
+            if self.is_ours(&entry, kind) {
+                continue;
+            }
+            if entry.host().is_empty() {
+                main_domains.push(entry);
+            } else {
+                sub_domains.push(entry);
+            }
+        }
+        for ip in ips {
+            let entry = Entry {
+                subhost: Some(self.host.clone()),
+                kind: kind.to_owned(),
+                value: ip.to_string(),
+                value2: None,
+            };
+            if self.host.is_empty() {
+                main_domains.push(entry);
+            } else {
+                sub_domains.push(entry);
+            }
+        }
+
+        // the ttl is shared by the whole domain
+        let ttl = settings.ttl.unwrap_or_else(|| ttl.to_string());
+        let mut params = vec![("ttl".to_owned(), ttl)];
+        for (i, entry) in main_domains.into_iter().enumerate() {
+            params.push((format!("main_record_type{}", i), entry.kind.to_lowercase()));
+            params.push((format!("main_record{}", i), entry.value));
+            if let Some(value2) = entry.value2 {
+                params.push((format!("main_recordx{}", i), value2));
+            }
+        }
+        for (i, entry) in sub_domains.into_iter().enumerate() {
+            params.push((format!("subdomain{}", i), entry.host().to_owned()));
+            params.push((format!("sub_record_type{}", i), entry.kind.to_lowercase()));
+            params.push((format!("sub_record{}", i), entry.value));
+            if let Some(value2) = entry.value2 {
+                params.push((format!("sub_recordx{}", i), value2));
+            }
+        }
+        debug!("writing back {} dynadot record param(s)", params.len());
+        self.call("set_dns2", &params).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Provider for Dynadot {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        self.get_records(record_type_from_family(family)).await
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        create_in_record_set(self, ip, ttl).await
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        update_in_record_set(self, &record.ip, ip, record.ttl).await
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        delete_from_record_set(self, &record.ip, record.ttl).await
+    }
+
+    fn supports_replace(&self) -> bool {
+        true
+    }
+
+    async fn replace_dns_records(
+        &self,
+        family: IpType,
+        _records: &[Self::DNSRecord],
+        ips: &[IpAddr],
+        ttl: u32,
+    ) -> Result<()> {
+        self.set_records(record_type_from_family(family), ips, ttl).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn keep_api_key_out_of_errors() {
+        // nothing listens on the discard port
+        let provider = Dynadot::create("secret-key", "home.example.com", None, Some("http://127.0.0.1:9"))
+            .await
+            .unwrap();
+        let err = provider.get_dns_record(IpType::V4).await.unwrap_err();
+        assert!(!format!("{:?}", err).contains("secret-key"));
+    }
+}
@@ -0,0 +1,229 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use log::{debug, warn};
+use reqwest::Client;
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
+
+use super::{create_in_record_set, delete_from_record_set, lookup_dns_record, update_in_record_set, Provider};
+use crate::IpType;
+
+/// The protocol has no TTL, the record set helpers are given this one
+const NO_TTL: u32 = 0;
+/// How long to stay away from the server after it answered `911`
+const SERVER_ERROR_BACKOFF: Duration = Duration::from_secs(30 * 60);
+/// How long to stay away from the server after it answered `abuse`
+const ABUSE_BACKOFF: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The protocol has no record id, every record is just an IP of the hostname
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub ip: IpAddr,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.ip)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+/// The failure return codes of the dyndns2 protocol
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Dyndns2Error {
+    BadAuth,
+    NoHost,
+    NotFqdn,
+    BadAgent,
+    NumHost,
+    DnsErr,
+    Abuse,
+    ServerError,
+    Unknown(String),
+}
+
+impl Display for Dyndns2Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Dyndns2Error::BadAuth => write!(f, "badauth: the username or password is wrong"),
+            Dyndns2Error::NoHost => write!(f, "nohost: the hostname doesn't exist in this account"),
+            Dyndns2Error::NotFqdn => write!(f, "notfqdn: the hostname is not a fully-qualified domain name"),
+            Dyndns2Error::BadAgent => write!(f, "badagent: the user agent is blocked"),
+            Dyndns2Error::NumHost => write!(f, "numhost: too many hosts in one update"),
+            Dyndns2Error::DnsErr => write!(f, "dnserr: dns error on the server"),
+            Dyndns2Error::Abuse => write!(f, "abuse: the hostname is blocked for update abuse"),
+            Dyndns2Error::ServerError => write!(f, "911: problem on the server"),
+            Dyndns2Error::Unknown(v) => write!(f, "unknown response: {}", v),
+        }
+    }
+}
+
+impl std::error::Error for Dyndns2Error {}
+
+impl Dyndns2Error {
+    fn backoff(&self) -> Option<Duration> {
+        match self {
+            Dyndns2Error::Abuse => Some(ABUSE_BACKOFF),
+            Dyndns2Error::ServerError => Some(SERVER_ERROR_BACKOFF),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    ipv4: Option<Vec<IpAddr>>,
+    ipv6: Option<Vec<IpAddr>>,
+    backoff_until: Option<Instant>,
+}
+
+pub struct Dyndns2 {
+    url: String,
+    dns: String,
+    username: String,
+    password: String,
+    client: Client,
+    /// The IPs last accepted by the server, which can't be read back with the protocol
+    state: Mutex<State>,
+}
+
+impl Dyndns2 {
+    pub async fn create<U: AsRef<str>, D: AsRef<str>, S: AsRef<str>>(
+        url: U,
+        dns: D,
+        username: S,
+        password: S,
+    ) -> Result<Self> {
+        let client = Client::builder()
+            .user_agent(concat!("ddns-rs/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(Dyndns2 {
+            url: url.as_ref().to_owned(),
+            dns: dns.as_ref().trim_end_matches('.').to_owned(),
+            username: username.as_ref().to_owned(),
+            password: password.as_ref().to_owned(),
+            client,
+            state: Mutex::new(State::default()),
+        })
+    }
+
+    async fn current_ips(&self, family: IpType) -> Result<Vec<IpAddr>> {
+        let cached = {
+            let state = self.state.lock().await;
+            match family {
+                IpType::V4 => state.ipv4.clone(),
+                IpType::V6 => state.ipv6.clone(),
+            }
+        };
+        match cached {
+            Some(ips) => Ok(ips),
+            None => lookup_dns_record(&self.dns, family).await,
+        }
+    }
+
+    async fn send_update(&self, family: IpType, ips: &[IpAddr]) -> Result<()> {
+        if ips.is_empty() {
+            bail!("dyndns2 can't delete the last record of {}", self.dns);
+        }
+        let mut state = self.state.lock().await;
+        if let Some(until) = state.backoff_until {
+            if Instant::now() < until {
+                bail!(
+                    "backing off dyndns2 update for another {} second(s)",
+                    (until - Instant::now()).as_secs()
+                );
+            }
+            state.backoff_until = None;
+        }
+
+        let myip = ips.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",");
+        let text = self
+            .client
+            .get(&self.url)
+            .basic_auth(&self.username, Some(&self.password))
+            .query(&[("hostname", &*self.dns), ("myip", &myip)])
+            .send()
+            .await?
+            .text()
+            .await?;
+        debug!("dyndns2 response: {}", text.trim());
+
+        for line in text.lines().map(str::trim).filter(|v| !v.is_empty()) {
+            let code = line.split_whitespace().next().unwrap_or_default();
+            let err = match code {
+                "good" | "nochg" => continue,
+                "badauth" => Dyndns2Error::BadAuth,
+                "nohost" => Dyndns2Error::NoHost,
+                "notfqdn" => Dyndns2Error::NotFqdn,
+                "badagent" => Dyndns2Error::BadAgent,
+                "numhost" => Dyndns2Error::NumHost,
+                "dnserr" => Dyndns2Error::DnsErr,
+                "abuse" => Dyndns2Error::Abuse,
+                "911" => Dyndns2Error::ServerError,
+                _ => Dyndns2Error::Unknown(line.to_owned()),
+            };
+            if let Some(backoff) = err.backoff() {
+                warn!("dyndns2 server answered {}, backing off for {:?}", code, backoff);
+                state.backoff_until = Some(Instant::now() + backoff);
+            }
+            return Err(err.into());
+        }
+
+        match family {
+            IpType::V4 => state.ipv4 = Some(ips.to_vec()),
+            IpType::V6 => state.ipv6 = Some(ips.to_vec()),
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Provider for Dyndns2 {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        Ok(self
+            .current_ips(family)
+            .await?
+            .into_iter()
+            .map(|ip| DNSRecord {
+                ip,
+            })
+            .collect())
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        create_in_record_set(self, ip, ttl).await
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        update_in_record_set(self, &record.ip, ip, NO_TTL).await
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        delete_from_record_set(self, &record.ip, NO_TTL).await
+    }
+
+    fn supports_replace(&self) -> bool {
+        true
+    }
+
+    async fn replace_dns_records(
+        &self,
+        family: IpType,
+        _records: &[Self::DNSRecord],
+        ips: &[IpAddr],
+        _ttl: u32,
+    ) -> Result<()> {
+        self.send_update(family, ips).await
+    }
+}
@@ -0,0 +1,362 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::Mutex;
+
+use super::{family_of, Provider};
+use crate::IpType;
+
+/// Sent in every request so plugins can reject a protocol they don't know
+const PROTOCOL_VERSION: u32 = 1;
+const DEFAULT_TIMEOUT: u64 = 30;
+
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub ip: IpAddr,
+    /// The opaque handle returned by the plugin, kept as serialized json
+    pub handle: String,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.ip)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct RecordMessage {
+    ip: IpAddr,
+    #[serde(default)]
+    handle: Value,
+}
+
+impl TryFrom<&DNSRecord> for RecordMessage {
+    type Error = anyhow::Error;
+
+    fn try_from(record: &DNSRecord) -> Result<Self> {
+        Ok(RecordMessage {
+            ip: record.ip,
+            handle: serde_json::from_str(&record.handle)?,
+        })
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "method", rename_all = "snake_case")]
+enum Request {
+    Get { family: &'static str },
+    Create { ip: IpAddr, ttl: u32 },
+    Update { record: RecordMessage, ip: IpAddr },
+    Delete { record: RecordMessage },
+}
+
+#[derive(Serialize, Debug)]
+struct Envelope<'a> {
+    version: u32,
+    /// The dns name the records belong to
+    name: &'a str,
+    #[serde(flatten)]
+    request: &'a Request,
+}
+
+#[derive(Deserialize, Debug)]
+struct Response {
+    error: Option<String>,
+    #[serde(default)]
+    records: Vec<RecordMessage>,
+}
+
+struct Process {
+    /// Killed when dropped
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+pub struct Exec {
+    command: Vec<String>,
+    dns: String,
+    timeout: Duration,
+    long_lived: bool,
+    /// The running plugin in the long-lived mode, it's spawned again after an error
+    process: Mutex<Option<Process>>,
+}
+
+impl Exec {
+    pub async fn create<D: AsRef<str>>(
+        command: Vec<String>,
+        dns: D,
+        long_lived: bool,
+        timeout: Option<u64>,
+    ) -> Result<Self> {
+        if command.is_empty() {
+            bail!("exec provider command is empty")
+        }
+        Ok(Exec {
+            command,
+            dns: dns.as_ref().trim_end_matches('.').to_owned(),
+            timeout: Duration::from_secs(timeout.unwrap_or(DEFAULT_TIMEOUT)),
+            long_lived,
+            process: Mutex::new(None),
+        })
+    }
+
+    fn spawn(&self) -> Result<Process> {
+        debug!("spawning plugin {}", self.command.join(" "));
+        let mut child = Command::new(&self.command[0])
+            .args(&self.command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|err| anyhow!("can't spawn plugin {}: {}", self.command[0], err))?;
+        let stdin = child.stdin.take().ok_or_else(|| anyhow!("plugin has no stdin"))?;
+        let stdout = child.stdout.take().ok_or_else(|| anyhow!("plugin has no stdout"))?;
+        Ok(Process {
+            _child: child,
+            stdin,
+            stdout: BufReader::new(stdout),
+        })
+    }
+
+    /// Write one request line and read one response line
+    async fn exchange(process: &mut Process, line: &str, close: bool) -> Result<String> {
+        process.stdin.write_all(line.as_bytes()).await?;
+        process.stdin.write_all(b"\n").await?;
+        process.stdin.flush().await?;
+        if close {
+            process.stdin.shutdown().await?;
+        }
+        let mut response = String::new();
+        if process.stdout.read_line(&mut response).await? == 0 {
+            bail!("plugin exited without a response")
+        }
+        Ok(response)
+    }
+
+    async fn call(&self, request: Request) -> Result<Response> {
+        let line = serde_json::to_string(&Envelope {
+            version: PROTOCOL_VERSION,
+            name: &self.dns,
+            request: &request,
+        })?;
+        debug!("plugin request {}", line);
+        let response = tokio::time::timeout(self.timeout, async {
+            if self.long_lived {
+                let mut guard = self.process.lock().await;
+                // taken out so a failed or timed out plugin is dropped and spawned again next time
+                let mut process = match guard.take() {
+                    Some(process) => process,
+                    None => self.spawn()?,
+                };
+                let response = Self::exchange(&mut process, &line, false).await?;
+                *guard = Some(process);
+                Ok::<_, anyhow::Error>(response)
+            } else {
+                let mut process = self.spawn()?;
+                Self::exchange(&mut process, &line, true).await
+            }
+        })
+        .await
+        .map_err(|_| anyhow!("plugin {} timed out", self.command[0]))??;
+        debug!("plugin response {}", response.trim_end());
+
+        let response = serde_json::from_str::<Response>(response.trim_end())
+            .map_err(|err| anyhow!("plugin response illegal: {}", err))?;
+        if let Some(error) = response.error {
+            bail!("plugin error: {}", error);
+        }
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl Provider for Exec {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        let request = Request::Get {
+            family: match family {
+                IpType::V4 => "ipv4",
+                IpType::V6 => "ipv6",
+            },
+        };
+        let response = self.call(request).await?;
+        Ok(response
+            .records
+            .into_iter()
+            .filter(|v| family_of(&v.ip) == family)
+            .map(|v| DNSRecord {
+                ip: v.ip,
+                handle: v.handle.to_string(),
+            })
+            .collect())
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        self.call(Request::Create {
+            ip: *ip,
+            ttl,
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        self.call(Request::Update {
+            record: record.try_into()?,
+            ip: *ip,
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        self.call(Request::Delete {
+            record: record.try_into()?,
+        })
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("ddns-rs-exec-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    async fn plugin(script: &str, long_lived: bool, timeout: Option<u64>) -> Exec {
+        let command = vec!["sh".to_owned(), "-c".to_owned(), script.to_owned()];
+        Exec::create(command, "home.example.com.", long_lived, timeout)
+            .await
+            .unwrap()
+    }
+
+    fn record(ip: &str, handle: &str) -> DNSRecord {
+        DNSRecord {
+            ip: ip.parse().unwrap(),
+            handle: handle.to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn request_envelope() {
+        let path = temp_path("envelope");
+        let script = format!(
+            r#"while read -r line; do echo "$line" >> {}; echo '{{}}'; done"#,
+            path.display()
+        );
+        let exec = plugin(&script, false, None).await;
+        exec.get_dns_record(IpType::V6).await.unwrap();
+        exec.create_dns_record(&"192.0.2.1".parse().unwrap(), 300)
+            .await
+            .unwrap();
+        exec.update_dns_record(&record("192.0.2.1", r#"{"id":7}"#), &"192.0.2.2".parse().unwrap())
+            .await
+            .unwrap();
+        exec.delete_dns_record(&record("192.0.2.2", "null")).await.unwrap();
+
+        let requests = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|v| serde_json::from_str::<Value>(v).unwrap())
+            .collect::<Vec<_>>();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(
+            requests,
+            [
+                json!({"version": 1, "name": "home.example.com", "method": "get", "family": "ipv6"}),
+                json!({"version": 1, "name": "home.example.com", "method": "create", "ip": "192.0.2.1", "ttl": 300}),
+                json!({
+                    "version": 1,
+                    "name": "home.example.com",
+                    "method": "update",
+                    "record": {"ip": "192.0.2.1", "handle": {"id": 7}},
+                    "ip": "192.0.2.2",
+                }),
+                json!({
+                    "version": 1,
+                    "name": "home.example.com",
+                    "method": "delete",
+                    "record": {"ip": "192.0.2.2", "handle": null},
+                }),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn responses() {
+        let records = r#"{"records": [{"ip": "192.0.2.1", "handle": {"id": 7}}, {"ip": "2001:db8::1"}]}"#;
+        let exec = plugin(&format!("read -r line; echo '{}'", records), false, None).await;
+        assert_eq!(
+            exec.get_dns_record(IpType::V4).await.unwrap(),
+            [record("192.0.2.1", r#"{"id":7}"#)]
+        );
+        assert_eq!(
+            exec.get_dns_record(IpType::V6).await.unwrap(),
+            [record("2001:db8::1", "null")]
+        );
+
+        let exec = plugin(r#"read -r line; echo '{"error": "zone locked"}'"#, false, None).await;
+        let err = exec.get_dns_record(IpType::V4).await.unwrap_err();
+        assert_eq!(err.to_string(), "plugin error: zone locked");
+
+        let exec = plugin("read -r line; echo done", false, None).await;
+        let err = exec.get_dns_record(IpType::V4).await.unwrap_err();
+        assert!(err.to_string().starts_with("plugin response illegal"), "{}", err);
+
+        let exec = plugin("read -r line; exit 1", false, None).await;
+        let err = exec.get_dns_record(IpType::V4).await.unwrap_err();
+        assert_eq!(err.to_string(), "plugin exited without a response");
+    }
+
+    #[tokio::test]
+    async fn timeout() {
+        let exec = plugin("read -r line; sleep 10", false, Some(1)).await;
+        let err = exec.get_dns_record(IpType::V4).await.unwrap_err();
+        assert_eq!(err.to_string(), "plugin sh timed out");
+    }
+
+    #[tokio::test]
+    async fn long_lived_respawns() {
+        let path = temp_path("spawns");
+        // every spawn is counted, a delete makes the plugin exit
+        let script = format!(
+            r#"echo spawn >> {}; while read -r line; do case "$line" in *delete*) exit 1;; esac; echo '{{}}'; done"#,
+            path.display()
+        );
+        let exec = plugin(&script, true, None).await;
+        let spawns = || std::fs::read_to_string(&path).unwrap().lines().count();
+        exec.get_dns_record(IpType::V4).await.unwrap();
+        exec.get_dns_record(IpType::V6).await.unwrap();
+        assert_eq!(spawns(), 1);
+        assert!(exec.delete_dns_record(&record("192.0.2.1", "null")).await.is_err());
+        exec.get_dns_record(IpType::V4).await.unwrap();
+        assert_eq!(spawns(), 2);
+        let _ = std::fs::remove_file(&path);
+    }
+}
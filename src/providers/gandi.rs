@@ -0,0 +1,173 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::Deserialize;
+
+use super::{
+    create_in_record_set,
+    delete_from_record_set,
+    record_type_from_family,
+    relative_name,
+    update_in_record_set,
+    zone_of,
+    Provider,
+};
+use crate::IpType;
+
+const DEFAULT_API_URL: &str = "https://api.gandi.net/v5/livedns";
+
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub ip: IpAddr,
+    pub ttl: u32,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.ip, self.ttl)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorResponse {
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct RecordSet {
+    rrset_ttl: u32,
+    #[serde(default)]
+    rrset_values: Vec<String>,
+}
+
+pub struct Gandi {
+    domain: String,
+    name: String,
+    api_url: String,
+    token: String,
+    client: Client,
+}
+
+impl Gandi {
+    pub async fn create<T: AsRef<str>, D: AsRef<str>>(
+        token: T,
+        dns: D,
+        domain: Option<&str>,
+        api_url: Option<&str>,
+    ) -> Result<Self> {
+        let dns = dns.as_ref().trim_end_matches('.').to_owned();
+
+        let domain = zone_of(&dns, domain)?;
+        let name = relative_name(&dns, &domain)?;
+        let name = if name.is_empty() { "@".to_owned() } else { name };
+
+        Ok(Gandi {
+            domain,
+            name,
+            api_url: api_url.unwrap_or(DEFAULT_API_URL).trim_end_matches('/').to_owned(),
+            token: token.as_ref().to_owned(),
+            client: Client::new(),
+        })
+    }
+
+    fn url(&self, kind: &str) -> String {
+        format!(
+            "{}/domains/{}/records/{}/{}",
+            self.api_url, self.domain, self.name, kind
+        )
+    }
+
+    /// `Ok(None)` means the record set doesn't exist
+    async fn send(&self, builder: RequestBuilder) -> Result<Option<String>> {
+        let response = builder.bearer_auth(&self.token).send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            match serde_json::from_str::<ErrorResponse>(&text) {
+                Ok(err) => bail!("gandi error {}: {}", status, err.message),
+                Err(_) => bail!("gandi error {}: {}", status, text),
+            }
+        }
+        Ok(Some(text))
+    }
+
+    async fn get_record_set(&self, kind: &str) -> Result<Vec<DNSRecord>> {
+        let text = match self.send(self.client.get(self.url(kind))).await? {
+            Some(text) => text,
+            None => return Ok(vec![]),
+        };
+        let rrset = serde_json::from_str::<RecordSet>(&text)?;
+        let mut records = vec![];
+        for value in rrset.rrset_values {
+            records.push(DNSRecord {
+                ip: value.parse()?,
+                ttl: rrset.rrset_ttl,
+            });
+        }
+        Ok(records)
+    }
+
+    async fn put_record_set(&self, kind: &str, ttl: u32, ips: &[IpAddr]) -> Result<()> {
+        if ips.is_empty() {
+            self.send(self.client.delete(self.url(kind))).await?;
+        } else {
+            let json = json!({
+                "rrset_values": ips.iter().map(|v| v.to_string()).collect::<Vec<_>>(),
+                "rrset_ttl": ttl,
+            });
+            self.send(self.client.put(self.url(kind)).json(&json))
+                .await?
+                .ok_or_else(|| anyhow!("gandi error: domain {} not found", self.domain))?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Provider for Gandi {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        self.get_record_set(record_type_from_family(family)).await
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        create_in_record_set(self, ip, ttl).await
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        update_in_record_set(self, &record.ip, ip, record.ttl).await
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        delete_from_record_set(self, &record.ip, record.ttl).await
+    }
+
+    fn supports_replace(&self) -> bool {
+        true
+    }
+
+    async fn replace_dns_records(
+        &self,
+        family: IpType,
+        records: &[Self::DNSRecord],
+        ips: &[IpAddr],
+        ttl: u32,
+    ) -> Result<()> {
+        let ttl = records.first().map(|v| v.ttl).unwrap_or(ttl);
+        self.put_record_set(record_type_from_family(family), ttl, ips).await
+    }
+}
@@ -0,0 +1,366 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use base64::engine::general_purpose::{STANDARD as BASE64, URL_SAFE_NO_PAD as BASE64_URL};
+use base64::Engine;
+use log::debug;
+use reqwest::{Client, RequestBuilder};
+use ring::rand::SystemRandom;
+use ring::signature::{RsaKeyPair, RSA_PKCS1_SHA256};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::sync::Mutex;
+use tokio::time::{sleep, Instant};
+
+use super::{create_in_record_set, delete_from_record_set, record_type_from_family, update_in_record_set, Provider};
+use crate::IpType;
+
+const DEFAULT_API_URL: &str = "https://dns.googleapis.com/dns/v1";
+const SCOPE: &str = "https://www.googleapis.com/auth/ndev.clouddns.readwrite";
+const JWT_LIFETIME: u64 = 3600;
+/// Refresh the token a bit before it actually expires
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(300);
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const POLL_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub ip: IpAddr,
+    pub ttl: u32,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.ip, self.ttl)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ServiceAccountKey {
+    project_id: Option<String>,
+    private_key_id: Option<String>,
+    private_key: String,
+    client_email: String,
+    token_uri: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorResponse {
+    error: ErrorDetail,
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorDetail {
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RecordSetsResponse {
+    #[serde(default)]
+    rrsets: Vec<RecordSet>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RecordSet {
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    ttl: u32,
+    #[serde(default)]
+    rrdatas: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Change {
+    id: String,
+    status: String,
+}
+
+struct Token {
+    access_token: String,
+    expires_at: Instant,
+}
+
+pub struct GCloud {
+    /// Fully qualified name with the trailing dot
+    name: String,
+    zone_url: String,
+    client_email: String,
+    private_key_id: Option<String>,
+    key_pair: RsaKeyPair,
+    token_url: String,
+    client: Client,
+    token: Mutex<Option<Token>>,
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|v| v.as_secs())
+        .unwrap_or_default()
+}
+
+impl GCloud {
+    pub async fn create<K: AsRef<Path>, Z: AsRef<str>, D: AsRef<str>>(
+        key_file: K,
+        managed_zone: Z,
+        dns: D,
+        project: Option<&str>,
+        api_url: Option<&str>,
+        token_url: Option<&str>,
+    ) -> Result<Self> {
+        let key_file = key_file.as_ref();
+        let content = tokio::fs::read_to_string(key_file)
+            .await
+            .map_err(|err| anyhow!("can't read service account key {}: {}", key_file.display(), err))?;
+        let key = serde_json::from_str::<ServiceAccountKey>(&content)?;
+
+        let der = key
+            .private_key
+            .lines()
+            .filter(|v| !v.starts_with("-----"))
+            .collect::<String>();
+        let der = BASE64.decode(der.trim())?;
+        let key_pair = RsaKeyPair::from_pkcs8(&der).map_err(|err| anyhow!("invalid service account key: {}", err))?;
+
+        let project = match project.map(|v| v.to_owned()).or(key.project_id) {
+            Some(project) => project,
+            None => bail!("no project in the service account key, set it by the project arg"),
+        };
+        let zone_url = format!(
+            "{}/projects/{}/managedZones/{}",
+            api_url.unwrap_or(DEFAULT_API_URL).trim_end_matches('/'),
+            project,
+            managed_zone.as_ref()
+        );
+
+        Ok(GCloud {
+            name: format!("{}.", dns.as_ref().trim_end_matches('.')),
+            zone_url,
+            client_email: key.client_email,
+            private_key_id: key.private_key_id,
+            key_pair,
+            token_url: token_url.map(|v| v.to_owned()).unwrap_or(key.token_uri),
+            client: Client::new(),
+            token: Mutex::new(None),
+        })
+    }
+
+    /// Sign the JWT assertion of the service account
+    fn assertion(&self) -> Result<String> {
+        let mut header = json!({"alg": "RS256", "typ": "JWT"});
+        if let Some(kid) = &self.private_key_id {
+            header["kid"] = json!(kid);
+        }
+        let now = unix_timestamp();
+        let claims = json!({
+            "iss": self.client_email,
+            "scope": SCOPE,
+            "aud": self.token_url,
+            "iat": now,
+            "exp": now + JWT_LIFETIME,
+        });
+        let message = format!(
+            "{}.{}",
+            BASE64_URL.encode(header.to_string()),
+            BASE64_URL.encode(claims.to_string())
+        );
+        let mut signature = vec![0; self.key_pair.public().modulus_len()];
+        self.key_pair
+            .sign(
+                &RSA_PKCS1_SHA256,
+                &SystemRandom::new(),
+                message.as_bytes(),
+                &mut signature,
+            )
+            .map_err(|err| anyhow!("can't sign jwt: {}", err))?;
+        Ok(format!("{}.{}", message, BASE64_URL.encode(signature)))
+    }
+
+    /// Get the cached access token, requesting a new one when it's about to expire
+    async fn access_token(&self) -> Result<String> {
+        let mut token = self.token.lock().await;
+        if let Some(token) = &*token {
+            if token.expires_at > Instant::now() {
+                return Ok(token.access_token.clone());
+            }
+        }
+
+        debug!("requesting gcloud access token");
+        let response = self
+            .client
+            .post(&self.token_url)
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", &self.assertion()?),
+            ])
+            .send()
+            .await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            match serde_json::from_str::<TokenErrorResponse>(&text) {
+                Ok(err) => bail!(
+                    "gcloud token error {}: {} {}",
+                    status,
+                    err.error,
+                    err.error_description.unwrap_or_default()
+                ),
+                Err(_) => bail!("gcloud token error {}: {}", status, text),
+            }
+        }
+        let response = serde_json::from_str::<TokenResponse>(&text)?;
+        let lifetime = Duration::from_secs(response.expires_in);
+        *token = Some(Token {
+            access_token: response.access_token.clone(),
+            expires_at: Instant::now() + lifetime.saturating_sub(TOKEN_REFRESH_MARGIN.min(lifetime / 2)),
+        });
+        Ok(response.access_token)
+    }
+
+    async fn send<T: DeserializeOwned>(&self, builder: RequestBuilder) -> Result<T> {
+        let response = builder.bearer_auth(self.access_token().await?).send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            match serde_json::from_str::<ErrorResponse>(&text) {
+                Ok(err) => bail!("gcloud error {}: {}", status, err.error.message),
+                Err(_) => bail!("gcloud error {}: {}", status, text),
+            }
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    async fn get_record_set(&self, kind: &str) -> Result<Vec<DNSRecord>> {
+        let url = format!("{}/rrsets", self.zone_url);
+        let response: RecordSetsResponse = self
+            .send(
+                self.client
+                    .get(url)
+                    .query(&[("name", self.name.as_str()), ("type", kind)]),
+            )
+            .await?;
+        let mut records = vec![];
+        for rrset in response
+            .rrsets
+            .into_iter()
+            .filter(|v| v.name == self.name && v.kind == kind)
+        {
+            for value in rrset.rrdatas {
+                records.push(DNSRecord {
+                    ip: value.parse()?,
+                    ttl: rrset.ttl,
+                });
+            }
+        }
+        Ok(records)
+    }
+
+    fn record_set(&self, kind: &str, ttl: u32, ips: &[IpAddr]) -> Value {
+        json!({
+            "name": self.name,
+            "type": kind,
+            "ttl": ttl,
+            "rrdatas": ips.iter().map(|v| v.to_string()).collect::<Vec<_>>(),
+        })
+    }
+
+    /// Replace the record set `olds` by `ips` in one atomic change, then wait until it's done
+    async fn change_record_set(&self, kind: &str, olds: &[DNSRecord], ttl: u32, ips: &[IpAddr]) -> Result<()> {
+        let mut deletions = vec![];
+        if let Some(old) = olds.first() {
+            let old_ips = olds.iter().map(|v| v.ip).collect::<Vec<_>>();
+            deletions.push(self.record_set(kind, old.ttl, &old_ips));
+        }
+        let mut additions = vec![];
+        if !ips.is_empty() {
+            additions.push(self.record_set(kind, ttl, ips));
+        }
+        if deletions.is_empty() && additions.is_empty() {
+            return Ok(());
+        }
+
+        let url = format!("{}/changes", self.zone_url);
+        let json = json!({
+            "additions": additions,
+            "deletions": deletions,
+        });
+        let mut change: Change = self.send(self.client.post(&url).json(&json)).await?;
+        let started = Instant::now();
+        while change.status != "done" {
+            if started.elapsed() > POLL_TIMEOUT {
+                bail!(
+                    "gcloud change {} is still {} after {:?}",
+                    change.id,
+                    change.status,
+                    POLL_TIMEOUT
+                );
+            }
+            debug!("gcloud change {} is {}", change.id, change.status);
+            sleep(POLL_INTERVAL).await;
+            change = self.send(self.client.get(format!("{}/{}", url, change.id))).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Provider for GCloud {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        self.get_record_set(record_type_from_family(family)).await
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        create_in_record_set(self, ip, ttl).await
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        update_in_record_set(self, &record.ip, ip, record.ttl).await
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        delete_from_record_set(self, &record.ip, record.ttl).await
+    }
+
+    fn supports_replace(&self) -> bool {
+        true
+    }
+
+    async fn replace_dns_records(
+        &self,
+        family: IpType,
+        records: &[Self::DNSRecord],
+        ips: &[IpAddr],
+        ttl: u32,
+    ) -> Result<()> {
+        let ttl = records.first().map(|v| v.ttl).unwrap_or(ttl);
+        self.change_record_set(record_type_from_family(family), records, ttl, ips)
+            .await
+    }
+}
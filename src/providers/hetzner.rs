@@ -0,0 +1,235 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use log::debug;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{record_type_from_ip, Provider};
+use crate::IpType;
+
+const DEFAULT_API_URL: &str = "https://dns.hetzner.com/api/v1";
+const PER_PAGE: u32 = 100;
+
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub id: String,
+    pub ip: IpAddr,
+    pub ttl: Option<u32>,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ZonesResponse {
+    #[serde(default)]
+    zones: Vec<Zone>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Zone {
+    id: String,
+    name: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct RecordsResponse {
+    #[serde(default)]
+    records: Vec<Record>,
+    meta: Option<Meta>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Meta {
+    pagination: Pagination,
+}
+
+#[derive(Deserialize, Debug)]
+struct Pagination {
+    last_page: u32,
+}
+
+#[derive(Deserialize, Debug)]
+struct Record {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    name: String,
+    value: String,
+    ttl: Option<u32>,
+}
+
+pub struct Hetzner {
+    name: String,
+    api_url: String,
+    token: String,
+    client: Client,
+    zone_identifier: String,
+}
+
+impl Hetzner {
+    pub async fn create<T: AsRef<str>, D: AsRef<str>>(token: T, dns: D, api_url: Option<&str>) -> Result<Self> {
+        let dns = dns.as_ref().trim_end_matches('.');
+        let mut provider = Hetzner {
+            name: String::new(),
+            api_url: api_url.unwrap_or(DEFAULT_API_URL).trim_end_matches('/').to_owned(),
+            token: token.as_ref().to_owned(),
+            client: Client::new(),
+            zone_identifier: String::new(),
+        };
+
+        // looked up by name like cloudflare, but every parent of `dns` is tried from the longest one instead
+        // of only the last two labels, so zones like example.co.uk and delegated subdomains are found
+        let labels = dns.split('.').collect::<Vec<_>>();
+        for i in 0..labels.len().saturating_sub(1) {
+            let zone_name = labels[i..].join(".");
+            let zone = match provider.find_zone(&zone_name).await? {
+                Some(zone) => zone,
+                None => continue,
+            };
+            debug!("zone name is {}", zone.name);
+            let name = labels[..i].join(".");
+            provider.name = if name.is_empty() { "@".to_owned() } else { name };
+            provider.zone_identifier = zone.id;
+            return Ok(provider);
+        }
+        bail!("can't find the zone of {}", dns)
+    }
+
+    async fn find_zone(&self, name: &str) -> Result<Option<Zone>> {
+        let url = format!("{}/zones", self.api_url);
+        let response = self
+            .client
+            .get(url)
+            .query(&[("name", name)])
+            .header("Auth-API-Token", &self.token)
+            .send()
+            .await?;
+        // a name that isn't a zone is answered with 404 rather than an empty list
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response: ZonesResponse = parse_response(response).await?;
+        Ok(response
+            .zones
+            .into_iter()
+            .find(|v| v.name.trim_end_matches('.') == name))
+    }
+
+    async fn send<T: DeserializeOwned>(&self, builder: RequestBuilder) -> Result<T> {
+        let response = builder.header("Auth-API-Token", &self.token).send().await?;
+        parse_response(response).await
+    }
+}
+
+async fn parse_response<T: DeserializeOwned>(response: Response) -> Result<T> {
+    let status = response.status();
+    let text = response.text().await?;
+    if !status.is_success() {
+        let message = serde_json::from_str::<Value>(&text)
+            .ok()
+            .and_then(|v| {
+                v.pointer("/error/message")
+                    .or_else(|| v.pointer("/message"))
+                    .and_then(|v| v.as_str().map(|v| v.to_owned()))
+            })
+            .unwrap_or(text);
+        bail!("hetzner error {}: {}", status, message);
+    }
+    if text.is_empty() {
+        return Ok(serde_json::from_value(Value::Null)?);
+    }
+    Ok(serde_json::from_str(&text)?)
+}
+
+#[async_trait]
+impl Provider for Hetzner {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        let url = format!("{}/records", self.api_url);
+        let mut result = vec![];
+        let mut current_page = 1;
+        loop {
+            let response: RecordsResponse = self
+                .send(self.client.get(&url).query(&[
+                    ("zone_id", self.zone_identifier.clone()),
+                    ("page", current_page.to_string()),
+                    ("per_page", PER_PAGE.to_string()),
+                ]))
+                .await?;
+
+            for record in &response.records {
+                if record.name != self.name {
+                    continue;
+                }
+                match (family, &*record.kind) {
+                    (IpType::V6, "AAAA") | (IpType::V4, "A") => {
+                        result.push(DNSRecord {
+                            id: record.id.clone(),
+                            ip: record.value.parse()?,
+                            ttl: record.ttl,
+                        });
+                    },
+                    _ => {},
+                }
+            }
+
+            match response.meta {
+                Some(meta) if current_page < meta.pagination.last_page => current_page += 1,
+                _ => break,
+            }
+        }
+        Ok(result)
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        let url = format!("{}/records", self.api_url);
+        let _: Value = self
+            .send(self.client.post(url).json(&json!({
+                "zone_id": self.zone_identifier,
+                "type": record_type_from_ip(ip),
+                "name": self.name,
+                "value": ip.to_string(),
+                "ttl": ttl,
+            })))
+            .await?;
+        Ok(())
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        let url = format!("{}/records/{}", self.api_url, record.id);
+        let mut json = json!({
+            "zone_id": self.zone_identifier,
+            "type": record_type_from_ip(ip),
+            "name": self.name,
+            "value": ip.to_string(),
+        });
+        if let Some(ttl) = record.ttl {
+            json["ttl"] = json!(ttl);
+        }
+        let _: Value = self.send(self.client.put(url).json(&json)).await?;
+        Ok(())
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        let url = format!("{}/records/{}", self.api_url, record.id);
+        let _: Value = self.send(self.client.delete(url)).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use log::debug;
+use reqwest::{Client, Method};
+use serde::Deserialize;
+
+use super::{family_of, percent_encode, record_type_from_ip, Provider};
+use crate::extractor::{Extractor, Fragment};
+use crate::IpType;
+
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    /// Empty when the list call has no `id` extractor
+    pub id: String,
+    pub ip: IpAddr,
+    /// Only known with a `ttl` extractor
+    pub ttl: Option<u32>,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.id.is_empty() {
+            write!(f, "{}", self.ip)
+        } else {
+            write!(f, "{}", self.id)
+        }
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+/// The whole provider as written in the config
+#[derive(Deserialize, Debug, Clone)]
+pub struct Config {
+    /// The value of `{name}`
+    pub dns: String,
+    /// Headers sent with every call
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    pub list: ListCall,
+    pub create: Call,
+    pub update: Call,
+    /// Without it records have to be deleted manually
+    pub delete: Option<Call>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Call {
+    #[serde(default = "default_method")]
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    pub body: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ListCall {
+    #[serde(flatten)]
+    pub call: Call,
+    /// Split the response into records, the whole response is one record when not set
+    pub records: Option<String>,
+    /// Only keep the records whose name is `{name}`, for apis listing the whole zone
+    pub name: Option<String>,
+    pub id: Option<String>,
+    pub ip: String,
+    /// The value of `{ttl}` in update
+    pub ttl: Option<String>,
+}
+
+fn default_method() -> String {
+    "GET".to_owned()
+}
+
+pub struct Http {
+    config: Config,
+    records: Option<Extractor>,
+    name: Option<Extractor>,
+    id: Option<Extractor>,
+    ip: Extractor,
+    ttl: Option<Extractor>,
+    client: Client,
+}
+
+impl Http {
+    pub async fn create(config: Config) -> Result<Self> {
+        let records = config.list.records.as_deref().map(|v| v.parse()).transpose()?;
+        let name = config.list.name.as_deref().map(|v| v.parse()).transpose()?;
+        let id = config.list.id.as_deref().map(|v| v.parse()).transpose()?;
+        let ip = config.list.ip.parse()?;
+        let ttl = config.list.ttl.as_deref().map(|v| v.parse()).transpose()?;
+        let update = &config.update;
+        let uses_ttl = std::iter::once(&update.url)
+            .chain(&update.body)
+            .chain(config.headers.values())
+            .chain(update.headers.values())
+            .any(|v| v.contains("{ttl}"));
+        if uses_ttl && ttl.is_none() {
+            bail!("http provider update uses {{ttl}}, it needs a ttl extractor in list")
+        }
+        Ok(Http {
+            config,
+            records,
+            name,
+            id,
+            ip,
+            ttl,
+            client: Client::new(),
+        })
+    }
+
+    /// Fill in the variables of a template, percent-encoded when it is a url
+    fn render(&self, template: &str, vars: &[(&str, String)], url: bool) -> String {
+        let encode = |value: &str| if url { percent_encode(value) } else { value.to_owned() };
+        let mut result = template.replace("{name}", &encode(&self.config.dns));
+        for (key, value) in vars {
+            result = result.replace(&format!("{{{}}}", key), &encode(value));
+        }
+        result
+    }
+
+    async fn send(&self, call: &Call, vars: &[(&str, String)]) -> Result<String> {
+        let method = call
+            .method
+            .to_ascii_uppercase()
+            .parse::<Method>()
+            .map_err(|err| anyhow!("invalid method {}: {}", call.method, err))?;
+        let url = self.render(&call.url, vars, true);
+        debug!("http provider calling {} {}", method, url);
+        let mut builder = self.client.request(method, url);
+        for (key, value) in self.config.headers.iter().chain(&call.headers) {
+            builder = builder.header(key, self.render(value, vars, false));
+        }
+        if let Some(body) = &call.body {
+            builder = builder.body(self.render(body, vars, false));
+        }
+        let response = builder.send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            bail!("http provider error {}: {}", status, text);
+        }
+        Ok(text)
+    }
+}
+
+#[async_trait]
+impl Provider for Http {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        let kind = match family {
+            IpType::V4 => "A",
+            IpType::V6 => "AAAA",
+        };
+        let text = self.send(&self.config.list.call, &[("type", kind.to_owned())]).await?;
+        let fragment = Fragment::parse(&text);
+        let fragments = match &self.records {
+            Some(records) => records.split(fragment)?,
+            None => vec![fragment],
+        };
+        let mut result = vec![];
+        for fragment in fragments {
+            if let Some(name) = &self.name {
+                let matched = name
+                    .extract(&fragment)
+                    .map(|v| {
+                        v.trim_end_matches('.')
+                            .eq_ignore_ascii_case(self.config.dns.trim_end_matches('.'))
+                    })
+                    .unwrap_or(false);
+                if !matched {
+                    continue;
+                }
+            }
+            // records of another type don't need to match the extractors
+            let ip = match self.ip.extract(&fragment).ok().and_then(|v| v.parse::<IpAddr>().ok()) {
+                Some(ip) => ip,
+                None => continue,
+            };
+            if family_of(&ip) != family {
+                continue;
+            }
+            let id = match &self.id {
+                Some(id) => id.extract(&fragment)?,
+                None => String::new(),
+            };
+            let ttl = match &self.ttl {
+                Some(ttl) => {
+                    let value = ttl.extract(&fragment)?;
+                    Some(value.parse().map_err(|err| anyhow!("ttl {} illegal: {}", value, err))?)
+                },
+                None => None,
+            };
+            result.push(DNSRecord {
+                id,
+                ip,
+                ttl,
+            });
+        }
+        Ok(result)
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        let vars = [
+            ("ip", ip.to_string()),
+            ("ttl", ttl.to_string()),
+            ("type", record_type_from_ip(ip).to_owned()),
+        ];
+        self.send(&self.config.create, &vars).await?;
+        Ok(())
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        let vars = [
+            ("id", record.id.clone()),
+            ("ip", ip.to_string()),
+            ("old_ip", record.ip.to_string()),
+            ("ttl", record.ttl.map(|v| v.to_string()).unwrap_or_default()),
+            ("type", record_type_from_ip(ip).to_owned()),
+        ];
+        self.send(&self.config.update, &vars).await?;
+        Ok(())
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        let delete = self
+            .config
+            .delete
+            .as_ref()
+            .ok_or_else(|| anyhow!("http provider has no delete call, remove {} manually", record.ip))?;
+        let vars = [
+            ("id", record.id.clone()),
+            ("ip", record.ip.to_string()),
+            ("type", record_type_from_ip(&record.ip).to_owned()),
+        ];
+        self.send(delete, &vars).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(update: &str, ttl: Option<&str>) -> Config {
+        let ttl = ttl.map(|v| format!("ttl = '{}'", v)).unwrap_or_default();
+        toml::from_str(&format!(
+            r#"
+            dns = "home.example.com"
+            create = {{ url = "http://127.0.0.1/records", body = "{{ip}}" }}
+            update = {{ method = "PUT", url = "http://127.0.0.1/records/{{id}}", body = '{}' }}
+            [list]
+            url = "http://127.0.0.1/records"
+            ip = "json:/content"
+            {}
+            "#,
+            update, ttl
+        ))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn update_ttl_needs_extractor() {
+        assert!(Http::create(config(r#"{"ttl": {ttl}}"#, None)).await.is_err());
+        assert!(Http::create(config(r#"{"ttl": {ttl}}"#, Some("json:/ttl")))
+            .await
+            .is_ok());
+        assert!(Http::create(config(r#"{"content": "{ip}"}"#, None)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn render_encodes_urls() {
+        let http = Http::create(config(r#"{"content": "{ip}"}"#, None)).await.unwrap();
+        let vars = [("id", "a b/c".to_owned()), ("ip", "2001:db8::1".to_owned())];
+        assert_eq!(
+            http.render("http://127.0.0.1/records/{id}?name={name}&ip={ip}", &vars, true),
+            "http://127.0.0.1/records/a%20b%2Fc?name=home.example.com&ip=2001%3Adb8%3A%3A1"
+        );
+        assert_eq!(
+            http.render(r#"{"id": "{id}", "content": "{ip}"}"#, &vars, false),
+            r#"{"id": "a b/c", "content": "2001:db8::1"}"#
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{find_domain_id, record_type_from_ip, relative_name, Provider};
+use crate::IpType;
+
+const DEFAULT_API_URL: &str = "https://api.linode.com/v4";
+const PAGE_SIZE: u32 = 500;
+
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub id: u64,
+    pub ip: IpAddr,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorResponse {
+    errors: Vec<ErrorItem>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorItem {
+    reason: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Page<T> {
+    #[serde(default = "Vec::new")]
+    data: Vec<T>,
+    page: u32,
+    pages: u32,
+}
+
+#[derive(Deserialize, Debug)]
+struct Domain {
+    id: u64,
+    domain: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Record {
+    id: u64,
+    #[serde(rename = "type")]
+    kind: String,
+    name: String,
+    target: String,
+}
+
+pub struct Linode {
+    name: String,
+    api_url: String,
+    token: String,
+    client: Client,
+    domain_id: u64,
+}
+
+impl Linode {
+    pub async fn create<T: AsRef<str>, D: AsRef<str>>(token: T, dns: D, api_url: Option<&str>) -> Result<Self> {
+        let dns = dns.as_ref();
+        let mut provider = Linode {
+            name: String::new(),
+            api_url: api_url.unwrap_or(DEFAULT_API_URL).trim_end_matches('/').to_owned(),
+            token: token.as_ref().to_owned(),
+            client: Client::new(),
+            domain_id: 0,
+        };
+
+        let url = format!("{}/domains", provider.api_url);
+        let domains = provider
+            .list::<Domain>(&url)
+            .await?
+            .into_iter()
+            .map(|v| (v.id, v.domain))
+            .collect::<Vec<_>>();
+        let (domain_id, domain) = find_domain_id(dns, &domains)?;
+
+        provider.name = relative_name(dns, &domain)?;
+        provider.domain_id = domain_id;
+        Ok(provider)
+    }
+
+    async fn send<T: DeserializeOwned>(&self, builder: RequestBuilder) -> Result<T> {
+        let response = builder.bearer_auth(&self.token).send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            match serde_json::from_str::<ErrorResponse>(&text) {
+                Ok(err) => {
+                    let reasons = err.errors.into_iter().map(|v| v.reason).collect::<Vec<_>>();
+                    bail!("linode error {}: {}", status, reasons.join(", "))
+                },
+                Err(_) => bail!("linode error {}: {}", status, text),
+            }
+        }
+        if text.is_empty() {
+            return Ok(serde_json::from_value(Value::Null)?);
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    async fn list<T: DeserializeOwned>(&self, url: &str) -> Result<Vec<T>> {
+        let mut result = vec![];
+        let mut current_page = 1;
+        loop {
+            let response: Page<T> = self
+                .send(
+                    self.client
+                        .get(url)
+                        .query(&[("page", current_page), ("page_size", PAGE_SIZE)]),
+                )
+                .await?;
+            result.extend(response.data);
+            if response.page >= response.pages {
+                break;
+            }
+            current_page = response.page + 1;
+        }
+        Ok(result)
+    }
+}
+
+#[async_trait]
+impl Provider for Linode {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        let url = format!("{}/domains/{}/records", self.api_url, self.domain_id);
+        let mut result = vec![];
+        for record in self.list::<Record>(&url).await? {
+            if record.name != self.name {
+                continue;
+            }
+            match (family, &*record.kind) {
+                (IpType::V6, "AAAA") | (IpType::V4, "A") => {
+                    result.push(DNSRecord {
+                        id: record.id,
+                        ip: record.target.parse()?,
+                    });
+                },
+                _ => {},
+            }
+        }
+        Ok(result)
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        let url = format!("{}/domains/{}/records", self.api_url, self.domain_id);
+        let _: Value = self
+            .send(self.client.post(url).json(&json!({
+                "type": record_type_from_ip(ip),
+                "name": self.name,
+                "target": ip.to_string(),
+                "ttl_sec": ttl,
+            })))
+            .await?;
+        Ok(())
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        let url = format!("{}/domains/{}/records/{}", self.api_url, self.domain_id, record.id);
+        let _: Value = self
+            .send(self.client.put(url).json(&json!({
+                "target": ip.to_string(),
+            })))
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        let url = format!("{}/domains/{}/records/{}", self.api_url, self.domain_id, record.id);
+        let _: Value = self.send(self.client.delete(url)).await?;
+        Ok(())
+    }
+}
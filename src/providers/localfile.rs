@@ -0,0 +1,387 @@
+use std::fmt::{Display, Formatter};
+use std::io::ErrorKind;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use log::info;
+
+use super::{
+    create_in_record_set,
+    delete_from_record_set,
+    family_of,
+    lock_local_files,
+    record_type_from_ip,
+    run_command,
+    update_in_record_set,
+    write_file_atomic,
+    Provider,
+};
+use crate::IpType;
+
+/// The TTL of a line without one, only unbound lines have a TTL
+const DEFAULT_TTL: u32 = 300;
+
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub ip: IpAddr,
+    pub ttl: u32,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.ip)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+/// The kind of file the managed entries are written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// `/etc/hosts`
+    Hosts,
+    /// A dnsmasq conf file, with `host-record=` lines
+    Dnsmasq,
+    /// An unbound include file, with `local-data:` lines
+    Unbound,
+}
+
+impl Format {
+    fn line(&self, dns: &str, ip: &IpAddr, ttl: u32) -> String {
+        match self {
+            Format::Hosts => format!("{}\t{}", ip, dns),
+            Format::Dnsmasq => format!("host-record={},{}", dns, ip),
+            Format::Unbound => format!("local-data: \"{}. {} IN {} {}\"", dns, ttl, record_type_from_ip(ip), ip),
+        }
+    }
+
+    /// The addresses of a managed line, `address=/name/ip` is accepted for dnsmasq too
+    fn parse(&self, line: &str) -> Vec<IpAddr> {
+        let values = match self {
+            Format::Hosts => line.split_whitespace().take(1).collect::<Vec<_>>(),
+            Format::Dnsmasq => match line.split_once('=') {
+                Some(("host-record", value)) => value.split(',').skip(1).collect(),
+                Some(("address", value)) => value.rsplit('/').take(1).collect(),
+                _ => vec![],
+            },
+            Format::Unbound => line
+                .trim_start_matches("local-data:")
+                .trim()
+                .trim_matches('"')
+                .split_whitespace()
+                .last()
+                .into_iter()
+                .collect(),
+        };
+        values.into_iter().filter_map(|v| v.trim().parse().ok()).collect()
+    }
+
+    /// The TTL of a managed line, only unbound has one
+    fn ttl(&self, line: &str) -> Option<u32> {
+        match self {
+            Format::Unbound => line
+                .trim_start_matches("local-data:")
+                .trim()
+                .trim_matches('"')
+                .split_whitespace()
+                .nth(1)
+                .and_then(|v| v.parse().ok()),
+            _ => None,
+        }
+    }
+}
+
+/// Where the managed block of a dns name sits in the file
+struct Block {
+    begin: usize,
+    /// The index of the end marker
+    end: usize,
+}
+
+pub struct LocalFile {
+    format: Format,
+    path: PathBuf,
+    dns: String,
+    begin_marker: String,
+    end_marker: String,
+    reload_command: Option<String>,
+}
+
+impl LocalFile {
+    pub async fn create<P: Into<PathBuf>, D: AsRef<str>>(
+        format: Format,
+        path: P,
+        dns: D,
+        reload_command: Option<&str>,
+    ) -> Result<Self> {
+        let dns = dns.as_ref().trim_end_matches('.').to_owned();
+        Ok(LocalFile {
+            format,
+            path: path.into(),
+            begin_marker: format!("# BEGIN ddns-rs {}", dns),
+            end_marker: format!("# END ddns-rs {}", dns),
+            dns,
+            reload_command: reload_command.map(|v| v.to_owned()),
+        })
+    }
+
+    /// A missing file is treated as an empty one, it's created on the first change
+    async fn load(&self) -> Result<Vec<String>> {
+        match tokio::fs::read_to_string(&self.path).await {
+            Ok(content) => Ok(content.lines().map(|v| v.to_owned()).collect()),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(vec![]),
+            Err(err) => Err(anyhow!("can't read {}: {}", self.path.display(), err)),
+        }
+    }
+
+    fn find_block(&self, lines: &[String]) -> Result<Option<Block>> {
+        let begin = match lines.iter().position(|v| v.trim() == self.begin_marker) {
+            Some(begin) => begin,
+            None => return Ok(None),
+        };
+        let end = lines[begin..]
+            .iter()
+            .position(|v| v.trim() == self.end_marker)
+            .ok_or_else(|| anyhow!("{} has no end marker for {}", self.path.display(), self.dns))?;
+        Ok(Some(Block {
+            begin,
+            end: begin + end,
+        }))
+    }
+
+    fn records(&self, lines: &[String], block: &Block) -> Vec<DNSRecord> {
+        let mut records = vec![];
+        for line in &lines[block.begin + 1..block.end] {
+            let line = line.trim();
+            let ttl = self.format.ttl(line).unwrap_or(DEFAULT_TTL);
+            records.extend(self.format.parse(line).into_iter().map(|ip| DNSRecord {
+                ip,
+                ttl,
+            }));
+        }
+        records
+    }
+
+    async fn current_records(&self) -> Result<Vec<DNSRecord>> {
+        let lines = self.load().await?;
+        Ok(match self.find_block(&lines)? {
+            Some(block) => self.records(&lines, &block),
+            None => vec![],
+        })
+    }
+
+    /// Replace the addresses of `family` in the managed block with `ips`, the block is added when missing
+    /// and removed once empty
+    fn splice(&self, lines: &mut Vec<String>, family: IpType, ips: &[IpAddr], ttl: u32) -> Result<()> {
+        let block = self.find_block(lines)?;
+        let mut records = match &block {
+            Some(block) => self
+                .records(lines, block)
+                .into_iter()
+                .filter(|v| family_of(&v.ip) != family)
+                .collect::<Vec<_>>(),
+            None => vec![],
+        };
+        records.extend(ips.iter().map(|ip| DNSRecord {
+            ip: *ip,
+            ttl,
+        }));
+        records.sort_by_key(|v| v.ip.is_ipv6());
+
+        let mut managed = vec![];
+        if !records.is_empty() {
+            managed.push(self.begin_marker.clone());
+            managed.extend(records.iter().map(|v| self.format.line(&self.dns, &v.ip, v.ttl)));
+            managed.push(self.end_marker.clone());
+        }
+        match block {
+            Some(block) => {
+                lines.splice(block.begin..=block.end, managed);
+            },
+            None => lines.extend(managed),
+        }
+        Ok(())
+    }
+
+    /// Rewrite the managed block with the addresses of `family` replaced by `ips`
+    async fn set_ips(&self, family: IpType, ips: &[IpAddr], ttl: u32) -> Result<()> {
+        let _guard = lock_local_files().await;
+        let mut lines = self.load().await?;
+        self.splice(&mut lines, family, ips, ttl)?;
+
+        let mut content = lines.join("\n");
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        write_file_atomic(&self.path, &content).await?;
+        info!("{} written", self.path.display());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Provider for LocalFile {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        Ok(self
+            .current_records()
+            .await?
+            .into_iter()
+            .filter(|v| family_of(&v.ip) == family)
+            .collect())
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        create_in_record_set(self, ip, ttl).await
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        update_in_record_set(self, &record.ip, ip, record.ttl).await
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        delete_from_record_set(self, &record.ip, record.ttl).await
+    }
+
+    fn supports_replace(&self) -> bool {
+        true
+    }
+
+    async fn replace_dns_records(
+        &self,
+        family: IpType,
+        _records: &[Self::DNSRecord],
+        ips: &[IpAddr],
+        ttl: u32,
+    ) -> Result<()> {
+        self.set_ips(family, ips, ttl).await
+    }
+
+    async fn commit_changes(&self) -> Result<()> {
+        if let Some(command) = &self.reload_command {
+            run_command(command).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn local_file(format: Format) -> LocalFile {
+        LocalFile::create(format, "/nonexistent", "nas.lan.", None)
+            .await
+            .unwrap()
+    }
+
+    fn lines(content: &str) -> Vec<String> {
+        content.lines().map(|v| v.to_owned()).collect()
+    }
+
+    fn ips(values: &[&str]) -> Vec<IpAddr> {
+        values.iter().map(|v| v.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn parse_lines() {
+        assert_eq!(Format::Hosts.parse("192.0.2.1\tnas.lan nas"), ips(&["192.0.2.1"]));
+        assert_eq!(Format::Hosts.parse("nas.lan"), ips(&[]));
+        assert_eq!(
+            Format::Dnsmasq.parse("host-record=nas.lan,192.0.2.1,2001:db8::1"),
+            ips(&["192.0.2.1", "2001:db8::1"])
+        );
+        assert_eq!(Format::Dnsmasq.parse("address=/nas.lan/192.0.2.1"), ips(&["192.0.2.1"]));
+        assert_eq!(Format::Dnsmasq.parse("server=192.0.2.53"), ips(&[]));
+        assert_eq!(
+            Format::Unbound.parse("local-data: \"nas.lan. 600 IN AAAA 2001:db8::1\""),
+            ips(&["2001:db8::1"])
+        );
+        assert_eq!(
+            Format::Unbound.ttl("local-data: \"nas.lan. 600 IN A 192.0.2.1\""),
+            Some(600)
+        );
+        assert_eq!(Format::Hosts.ttl("192.0.2.1\tnas.lan"), None);
+    }
+
+    #[test]
+    fn line_round_trip() {
+        let ip = "2001:db8::1".parse().unwrap();
+        for format in [Format::Hosts, Format::Dnsmasq, Format::Unbound] {
+            assert_eq!(format.parse(&format.line("nas.lan", &ip, 600)), [ip]);
+        }
+        assert_eq!(
+            Format::Unbound.ttl(&Format::Unbound.line("nas.lan", &ip, 600)),
+            Some(600)
+        );
+    }
+
+    #[tokio::test]
+    async fn splice_new_block() {
+        let provider = local_file(Format::Hosts).await;
+        let mut content = lines("127.0.0.1 localhost");
+        provider
+            .splice(&mut content, IpType::V4, &ips(&["192.0.2.1"]), 300)
+            .unwrap();
+        assert_eq!(
+            content,
+            lines("127.0.0.1 localhost\n# BEGIN ddns-rs nas.lan\n192.0.2.1\tnas.lan\n# END ddns-rs nas.lan")
+        );
+    }
+
+    #[tokio::test]
+    async fn splice_keeps_other_family() {
+        let provider = local_file(Format::Unbound).await;
+        let mut content = lines(concat!(
+            "server:\n",
+            "# BEGIN ddns-rs nas.lan\n",
+            "local-data: \"nas.lan. 600 IN A 192.0.2.1\"\n",
+            "local-data: \"nas.lan. 900 IN AAAA 2001:db8::1\"\n",
+            "# END ddns-rs nas.lan\n",
+            "local-data: \"other.lan. 600 IN A 192.0.2.9\""
+        ));
+        provider
+            .splice(&mut content, IpType::V4, &ips(&["192.0.2.2", "192.0.2.3"]), 300)
+            .unwrap();
+        assert_eq!(
+            content,
+            lines(concat!(
+                "server:\n",
+                "# BEGIN ddns-rs nas.lan\n",
+                "local-data: \"nas.lan. 300 IN A 192.0.2.2\"\n",
+                "local-data: \"nas.lan. 300 IN A 192.0.2.3\"\n",
+                "local-data: \"nas.lan. 900 IN AAAA 2001:db8::1\"\n",
+                "# END ddns-rs nas.lan\n",
+                "local-data: \"other.lan. 600 IN A 192.0.2.9\""
+            ))
+        );
+        let block = provider.find_block(&content).unwrap().unwrap();
+        assert_eq!(provider.records(&content, &block)[2].ttl, 900);
+    }
+
+    #[tokio::test]
+    async fn splice_removes_empty_block() {
+        let provider = local_file(Format::Dnsmasq).await;
+        let mut content =
+            lines("# BEGIN ddns-rs nas.lan\nhost-record=nas.lan,192.0.2.1\n# END ddns-rs nas.lan\nport=53");
+        provider.splice(&mut content, IpType::V4, &[], 300).unwrap();
+        assert_eq!(content, lines("port=53"));
+    }
+
+    #[tokio::test]
+    async fn splice_without_end_marker() {
+        let provider = local_file(Format::Hosts).await;
+        let mut content = lines("# BEGIN ddns-rs nas.lan\n192.0.2.1\tnas.lan");
+        assert!(provider
+            .splice(&mut content, IpType::V4, &ips(&["192.0.2.2"]), 300)
+            .is_err());
+    }
+}
@@ -1,20 +1,80 @@
-use std::cmp::Ordering;
-use std::collections::HashSet;
-use std::hash::{Hash, Hasher};
+use std::fmt::{Display, Formatter};
 use std::net::IpAddr;
+use std::path::Path;
 
-use anyhow::Result;
+use addr::parse_dns_name;
+use anyhow::{anyhow, bail, Result};
 use async_trait::async_trait;
-use log::info;
+use hmac::{Hmac, Mac};
+use log::{debug, info};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
 
+pub use self::aliyun::{Aliyun, Credentials as AliyunCredentials};
+pub use self::azure::{Azure, Credentials as AzureCredentials};
 pub use self::cloudflare::Cloudflare;
+pub use self::desec::Desec;
+pub use self::digitalocean::DigitalOcean;
+pub use self::dnspod::{Credentials as DnspodCredentials, Dnspod};
+pub use self::dryrun::DryRun;
+pub use self::duckdns::DuckDns;
+pub use self::dynadot::Dynadot;
+pub use self::dyndns2::Dyndns2;
+pub use self::exec::Exec;
 pub use self::fake::Fake;
+pub use self::gandi::Gandi;
+pub use self::gcloud::GCloud;
 pub use self::godaddy::Godaddy;
+pub use self::hetzner::Hetzner;
+pub use self::http::{Config as HttpConfig, Http};
+pub use self::linode::Linode;
+pub use self::localfile::{Format as LocalFileFormat, LocalFile};
+pub use self::namecheap::{Mode as NamecheapMode, Namecheap};
+pub use self::namecom::NameCom;
+pub use self::namesilo::NameSilo;
+pub use self::ovh::{Credentials as OvhCredentials, Ovh};
+pub use self::porkbun::{Credentials as PorkbunCredentials, Porkbun};
+pub use self::powerdns::PowerDns;
+pub use self::rfc2136::{Rfc2136, TsigAlgorithm};
+pub use self::route53::{Credentials as Route53Credentials, Route53};
+pub use self::vultr::Vultr;
+pub use self::zonefile::{SerialMode, ZoneFile};
+use crate::state::Ownership;
 use crate::IpType;
 
+mod aliyun;
+mod azure;
 mod cloudflare;
+mod desec;
+mod digitalocean;
+mod dnspod;
+mod dryrun;
+mod duckdns;
+mod dynadot;
+mod dyndns2;
+mod exec;
 mod fake;
+mod gandi;
+mod gcloud;
 mod godaddy;
+mod hetzner;
+mod http;
+mod linode;
+mod localfile;
+mod namecheap;
+mod namecom;
+mod namesilo;
+mod ovh;
+mod porkbun;
+mod powerdns;
+mod rfc2136;
+mod route53;
+mod vultr;
+mod zonefile;
+
+/// Characters that RFC 3986 leaves unescaped
+const UNRESERVED: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.').remove(b'~');
 
 #[async_trait]
 pub trait Provider: Send + Sync {
@@ -24,37 +84,108 @@ pub trait Provider: Send + Sync {
     async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()>;
     async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()>;
     async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()>;
+
+    /// Whether the provider can replace the whole record set of a family in one call,
+    /// if so `check_and_update` uses `replace_dns_records` instead of the per-record calls
+    fn supports_replace(&self) -> bool {
+        false
+    }
+
+    async fn replace_dns_records(
+        &self,
+        _family: IpType,
+        _records: &[Self::DNSRecord],
+        _ips: &[IpAddr],
+        _ttl: u32,
+    ) -> Result<()> {
+        bail!("the provider doesn't support replacing the whole record set")
+    }
+
+    /// Called once after `check_and_update` changed any record, for providers that have to
+    /// publish the changes explicitly
+    async fn commit_changes(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
-#[derive(Debug, Clone)]
-struct HashSetItem<'a, T: Provider> {
-    ip: &'a IpAddr,
-    ref_record: Option<&'a T::DNSRecord>,
+/// The changes to the records of a family, made by comparing the remote records with the target addresses
+#[derive(Debug)]
+pub(crate) struct ChangeSet {
+    family: IpType,
+    ttl: u32,
+    /// Whether the whole record set is replaced in one call instead of the per-record calls
+    replace: bool,
+    /// Rewritten with the same address, when `force` is set
+    forced: Vec<IpAddr>,
+    /// `(old, new)` pairs
+    updates: Vec<(IpAddr, IpAddr)>,
+    creates: Vec<IpAddr>,
+    deletes: Vec<IpAddr>,
+    /// Records ddns-rs doesn't own, they are left alone
+    unmanaged: Vec<IpAddr>,
+    /// The addresses owned once the changes are applied
+    owned: Vec<IpAddr>,
 }
 
-impl<'a, T: Provider> Hash for HashSetItem<'a, T> {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.ip.hash(state)
+impl ChangeSet {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.forced.is_empty() && self.updates.is_empty() && self.creates.is_empty() && self.deletes.is_empty()
     }
-}
 
-impl<'a, T: Provider> PartialOrd for HashSetItem<'a, T> {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.ip.partial_cmp(other.ip)
+    /// The addresses written by the changes, they are sent to the notifiers
+    pub(crate) fn changed_ips(&self) -> Vec<IpAddr> {
+        let mut ips = self.forced.clone();
+        ips.extend(self.updates.iter().map(|(_, new)| *new));
+        ips.extend_from_slice(&self.creates);
+        ips
     }
 }
 
-impl<'a, T: Provider> PartialEq<Self> for HashSetItem<'a, T> {
-    fn eq(&self, other: &Self) -> bool {
-        self.ip.eq(other.ip)
+impl Display for ChangeSet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for ip in &self.forced {
+            writeln!(f, "  ~ {} {} (forced)", record_type_from_ip(ip), ip)?;
+        }
+        for (old, new) in &self.updates {
+            writeln!(f, "  ~ {} {} -> {}", record_type_from_ip(new), old, new)?;
+        }
+        for ip in &self.creates {
+            writeln!(f, "  + {} {} ttl {}", record_type_from_ip(ip), ip, self.ttl)?;
+        }
+        for ip in &self.deletes {
+            writeln!(f, "  - {} {}", record_type_from_ip(ip), ip)?;
+        }
+        for ip in &self.unmanaged {
+            writeln!(f, "  = {} {} (unmanaged)", record_type_from_ip(ip), ip)?;
+        }
+        if self.is_empty() {
+            writeln!(f, "  no changes")?;
+        }
+        Ok(())
     }
 }
 
-impl<'a, T: Provider> Eq for HashSetItem<'a, T> {}
-
 #[async_trait]
 pub(crate) trait DynProvider: Send + Sync {
-    async fn check_and_update(&self, new_ips: &[IpAddr], ttl: u32, force: bool, family: IpType) -> Result<Vec<IpAddr>>;
+    /// Compare the remote records with `new_ips` without changing anything
+    async fn plan(
+        &self,
+        new_ips: &[IpAddr],
+        ttl: u32,
+        force: bool,
+        family: IpType,
+        ownership: &Ownership,
+    ) -> Result<ChangeSet>;
+
+    /// Plan and apply the changes, returns the addresses written
+    async fn check_and_update(
+        &self,
+        new_ips: &[IpAddr],
+        ttl: u32,
+        force: bool,
+        family: IpType,
+        ownership: &Ownership,
+    ) -> Result<Vec<IpAddr>>;
 }
 
 #[async_trait]
@@ -62,68 +193,338 @@ impl<P> DynProvider for P
 where
     P: Provider,
 {
-    async fn check_and_update(&self, new_ips: &[IpAddr], ttl: u32, force: bool, family: IpType) -> Result<Vec<IpAddr>> {
-        let mut real_used_ips = vec![];
-        let dns_records = self.get_dns_record(family).await?;
-        if dns_records.is_empty() {
-            info!("remote dns record(s) is empty");
-        } else {
-            let ips_str = dns_records
-                .iter()
-                .map(|v| v.as_ref().to_string())
-                .collect::<Vec<_>>()
-                .join(",");
-            info!("got dns record(s) from remote: [{}]", ips_str);
-        }
-        let new_ip_set: HashSet<_> = new_ips
-            .iter()
-            .map(|v| HashSetItem::<'_, P> {
-                ip: v,
-                ref_record: None,
-            })
-            .collect();
-        let dns_record_set: HashSet<_> = dns_records
+    async fn plan(
+        &self,
+        new_ips: &[IpAddr],
+        ttl: u32,
+        force: bool,
+        family: IpType,
+        ownership: &Ownership,
+    ) -> Result<ChangeSet> {
+        let (_, change_set) = make_plan(self, new_ips, ttl, force, family, ownership).await?;
+        Ok(change_set)
+    }
+
+    async fn check_and_update(
+        &self,
+        new_ips: &[IpAddr],
+        ttl: u32,
+        force: bool,
+        family: IpType,
+        ownership: &Ownership,
+    ) -> Result<Vec<IpAddr>> {
+        let (dns_records, change_set) = make_plan(self, new_ips, ttl, force, family, ownership).await?;
+        apply_plan(self, &dns_records, &change_set, ownership).await?;
+        Ok(change_set.changed_ips())
+    }
+}
+
+/// The planning phase, returns the remote records along with the changes to them
+async fn make_plan<P: Provider>(
+    provider: &P,
+    new_ips: &[IpAddr],
+    ttl: u32,
+    force: bool,
+    family: IpType,
+    ownership: &Ownership,
+) -> Result<(Vec<P::DNSRecord>, ChangeSet)> {
+    let dns_records = provider.get_dns_record(family).await?;
+    if dns_records.is_empty() {
+        info!("remote dns record(s) is empty");
+    } else {
+        let ips_str = dns_records
             .iter()
-            .map(|v| HashSetItem::<'_, P> {
-                ip: v.as_ref(),
-                ref_record: Some(v),
-            })
-            .collect();
-        let mut news: Vec<_> = new_ip_set.difference(&dns_record_set).collect();
-        let mut olds: Vec<_> = dns_record_set.difference(&new_ip_set).collect();
-        if force {
-            let sames: Vec<_> = dns_record_set.intersection(&new_ip_set).collect();
-            for item in sames {
-                let record = item.ref_record.unwrap();
-                let ip = item.ip;
-                info!("force updating dns record to {}", ip);
-                self.update_dns_record(record, ip).await?;
-                real_used_ips.push(*ip);
-            }
+            .map(|v| v.as_ref().to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        info!("got dns record(s) from remote: [{}]", ips_str);
+    }
+    // the records ddns-rs didn't write are never updated nor deleted, before the first run with the state
+    // file that's all of them unless `adopt_existing` is set
+    let owned = ownership.owned(family).await.unwrap_or_default();
+    let mut managed = vec![];
+    let mut unmanaged = vec![];
+    for record in &dns_records {
+        let ip = *record.as_ref();
+        let ips = if ownership.is_managed(&owned, &ip) {
+            &mut managed
+        } else {
+            &mut unmanaged
+        };
+        if !ips.contains(&ip) {
+            ips.push(ip);
         }
-        while let (Some(old_item), Some(new_item)) = (olds.get(0), news.get(0)) {
-            let record = old_item.ref_record.unwrap();
-            let new_ip = new_item.ip;
-            olds.remove(0);
-            news.remove(0);
-            info!("updating dns record to {}", new_ip);
-            self.update_dns_record(record, new_ip).await?;
-            real_used_ips.push(*new_ip);
+    }
+    if !unmanaged.is_empty() {
+        let ips_str = unmanaged.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",");
+        info!("leaving unmanaged dns record(s) alone: [{}]", ips_str);
+    }
+    // an address already held by an unmanaged record doesn't need one of ours
+    let mut target_ips = vec![];
+    for ip in new_ips {
+        if !unmanaged.contains(ip) && !target_ips.contains(ip) {
+            target_ips.push(*ip);
         }
-        for old_item in olds {
-            info!("target ip {} not belong to this interface, delete it", old_item.ip);
-            self.delete_dns_record(old_item.ref_record.unwrap()).await?;
+    }
+
+    let mut news = target_ips
+        .iter()
+        .filter(|v| !managed.contains(v))
+        .copied()
+        .collect::<Vec<_>>();
+    let mut olds = managed
+        .iter()
+        .filter(|v| !target_ips.contains(v))
+        .copied()
+        .collect::<Vec<_>>();
+    let forced = if force {
+        target_ips.iter().filter(|v| managed.contains(v)).copied().collect()
+    } else {
+        vec![]
+    };
+    let replace = provider.supports_replace();
+    let mut updates = vec![];
+    if !replace {
+        while !olds.is_empty() && !news.is_empty() {
+            updates.push((olds.remove(0), news.remove(0)));
         }
-        for new_item in news {
-            info!("target ip {} not exist in dns provider, create it", new_item.ip);
-            self.create_dns_record(new_item.ip, ttl).await?;
-            real_used_ips.push(*new_item.ip);
+    }
+    Ok((
+        dns_records,
+        ChangeSet {
+            family,
+            ttl,
+            replace,
+            forced,
+            updates,
+            creates: news,
+            deletes: olds,
+            unmanaged,
+            owned: target_ips,
+        },
+    ))
+}
+
+/// The apply phase, `dns_records` are the remote records the changes were planned with
+async fn apply_plan<P: Provider>(
+    provider: &P,
+    dns_records: &[P::DNSRecord],
+    change_set: &ChangeSet,
+    ownership: &Ownership,
+) -> Result<()> {
+    let family = change_set.family;
+    if change_set.is_empty() {
+        info!("remote and local are the same nothing to do");
+        ownership.set(family, &change_set.owned).await?;
+        return Ok(());
+    }
+    if change_set.replace {
+        let mut ips = change_set.owned.clone();
+        ips.extend_from_slice(&change_set.unmanaged);
+        let ips_str = ips.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",");
+        info!("replacing dns record(s) with [{}]", ips_str);
+        provider
+            .replace_dns_records(family, dns_records, &ips, change_set.ttl)
+            .await?;
+        ownership.set(family, &change_set.owned).await?;
+        return provider.commit_changes().await;
+    }
+
+    let find = |ip: &IpAddr| {
+        dns_records
+            .iter()
+            .find(|v| v.as_ref() == ip)
+            .ok_or_else(|| anyhow!("can't find the dns record of {}", ip))
+    };
+    for ip in &change_set.forced {
+        info!("force updating dns record to {}", ip);
+        provider.update_dns_record(find(ip)?, ip).await?;
+    }
+    for (old_ip, new_ip) in &change_set.updates {
+        info!("updating dns record to {}", new_ip);
+        provider.update_dns_record(find(old_ip)?, new_ip).await?;
+        ownership.claim(family, new_ip).await?;
+        ownership.release(family, old_ip).await?;
+    }
+    for ip in &change_set.deletes {
+        info!("target ip {} not belong to this interface, delete it", ip);
+        provider.delete_dns_record(find(ip)?).await?;
+        ownership.release(family, ip).await?;
+    }
+    for ip in &change_set.creates {
+        info!("target ip {} not exist in dns provider, create it", ip);
+        provider.create_dns_record(ip, change_set.ttl).await?;
+        ownership.claim(family, ip).await?;
+    }
+    ownership.set(family, &change_set.owned).await?;
+    provider.commit_changes().await
+}
+
+/// Resolve the current records of `dns` by the system resolver, for the providers that
+/// have no way to list records. A name that doesn't resolve is treated as having no record.
+pub(crate) async fn lookup_dns_record(dns: &str, family: IpType) -> Result<Vec<IpAddr>> {
+    let addrs = match tokio::net::lookup_host((dns, 0)).await {
+        Ok(addrs) => addrs,
+        Err(err) => {
+            debug!("can't resolve {}: {}", dns, err);
+            return Ok(vec![]);
+        },
+    };
+    let mut ips = vec![];
+    for addr in addrs {
+        let ip = addr.ip();
+        let matched = match family {
+            IpType::V4 => ip.is_ipv4(),
+            IpType::V6 => ip.is_ipv6(),
+        };
+        if matched && !ips.contains(&ip) {
+            ips.push(ip);
         }
-        if real_used_ips.is_empty() {
-            info!("remote and local are the same nothing to do");
+    }
+    Ok(ips)
+}
+
+lazy_static::lazy_static! {
+    /// Serializes the read-modify-write of local files, as several providers may edit the same file
+    static ref LOCAL_FILE_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::new(());
+}
+
+pub(crate) async fn lock_local_files() -> tokio::sync::MutexGuard<'static, ()> {
+    LOCAL_FILE_LOCK.lock().await
+}
+
+/// Replace the content of `path` by writing a temporary file next to it and renaming it over,
+/// so readers never see a half written file. The permissions of the old file are kept.
+pub(crate) async fn write_file_atomic(path: &Path, content: &str) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("invalid file path {}", path.display()))?
+        .to_string_lossy();
+    let tmp_path = path.with_file_name(format!(".{}.ddns-rs.tmp", file_name));
+    let mut file = tokio::fs::File::create(&tmp_path).await?;
+    file.write_all(content.as_bytes()).await?;
+    file.sync_all().await?;
+    drop(file);
+    if let Ok(metadata) = tokio::fs::metadata(path).await {
+        tokio::fs::set_permissions(&tmp_path, metadata.permissions()).await?;
+    }
+    if let Err(err) = tokio::fs::rename(&tmp_path, path).await {
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+        bail!("can't replace {}: {}", path.display(), err);
+    }
+    Ok(())
+}
+
+/// Run a command like `rndc reload example.com`, split on whitespace and not run through a shell
+pub(crate) async fn run_command(command: &str) -> Result<()> {
+    let mut args = command.split_whitespace();
+    let program = args.next().ok_or_else(|| anyhow!("empty command"))?;
+    debug!("running {}", command);
+    let output = tokio::process::Command::new(program).args(args).output().await?;
+    if !output.status.success() {
+        bail!(
+            "command {} failed with {}: {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Pick the domain that `dns` belongs to out of the `(id, name)` pairs listed by a provider, the longest
+/// match wins so a delegated subdomain is preferred over its parent. Returns the id and the domain name.
+pub(crate) fn find_domain_id<T: Clone>(dns: &str, domains: &[(T, String)]) -> Result<(T, String)> {
+    let dns = dns.trim_end_matches('.');
+    let mut found: Option<&(T, String)> = None;
+    for item in domains {
+        let name = item.1.trim_end_matches('.');
+        let matched = dns == name || dns.ends_with(&format!(".{}", name));
+        if matched && found.map(|v| v.1.len() < item.1.len()).unwrap_or(true) {
+            found = Some(item);
         }
-        Ok(real_used_ips)
     }
+    let (id, name) = match found {
+        Some(item) => item.clone(),
+        None => bail!("can't find the zone of {}", dns),
+    };
+    debug!("zone name is {}", name);
+    Ok((id, name.trim_end_matches('.').to_owned()))
+}
+
+/// The zone of `dns`, the configured one if any, else its registrable domain
+pub(crate) fn zone_of(dns: &str, zone: Option<&str>) -> Result<String> {
+    if let Some(zone) = zone {
+        return Ok(zone.trim_end_matches('.').to_owned());
+    }
+    let result = parse_dns_name(dns).map_err(|err| anyhow!("can't parse dns {}: {}", dns, err))?;
+    match result.root() {
+        Some(zone) => Ok(zone.to_owned()),
+        None => bail!("can't find the zone of {}", dns),
+    }
+}
+
+/// The name of `dns` relative to `domain`, empty for the apex
+pub(crate) fn relative_name(dns: &str, domain: &str) -> Result<String> {
+    let dns = dns.trim_end_matches('.');
+    let domain = domain.trim_end_matches('.');
+    if dns == domain {
+        return Ok(String::new());
+    }
+    match dns.strip_suffix(&format!(".{}", domain)) {
+        Some(name) => Ok(name.to_owned()),
+        None => bail!("{} is not in the zone {}", dns, domain),
+    }
+}
+
+/// Change the addresses of a family by reading the record set and writing it back whole with
+/// `replace_dns_records`, for the providers that store every address of a name and type as one set
+pub(crate) async fn modify_record_set<P, F>(provider: &P, family: IpType, ttl: u32, f: F) -> Result<()>
+where
+    P: Provider + ?Sized,
+    F: FnOnce(&mut Vec<IpAddr>) + Send,
+{
+    if !provider.supports_replace() {
+        bail!("the provider doesn't support replacing the whole record set")
+    }
+    let records = provider.get_dns_record(family).await?;
+    let mut ips = records.iter().map(|v| *v.as_ref()).collect::<Vec<_>>();
+    f(&mut ips);
+    provider.replace_dns_records(family, &records, &ips, ttl).await
+}
+
+/// `create_dns_record` of the providers that store every address of a name and type as one set
+pub(crate) async fn create_in_record_set<P: Provider + ?Sized>(provider: &P, ip: &IpAddr, ttl: u32) -> Result<()> {
+    modify_record_set(provider, family_of(ip), ttl, |ips| {
+        if !ips.contains(ip) {
+            ips.push(*ip);
+        }
+    })
+    .await
+}
+
+/// `update_dns_record` of the providers that store every address of a name and type as one set, `ttl` is the
+/// one the set is written back with
+pub(crate) async fn update_in_record_set<P: Provider + ?Sized>(
+    provider: &P,
+    old: &IpAddr,
+    ip: &IpAddr,
+    ttl: u32,
+) -> Result<()> {
+    modify_record_set(provider, family_of(ip), ttl, |ips| {
+        ips.retain(|v| v != old && v != ip);
+        ips.push(*ip);
+    })
+    .await
+}
+
+/// `delete_dns_record` of the providers that store every address of a name and type as one set, `ttl` is the
+/// one the set is written back with
+pub(crate) async fn delete_from_record_set<P: Provider + ?Sized>(provider: &P, ip: &IpAddr, ttl: u32) -> Result<()> {
+    modify_record_set(provider, family_of(ip), ttl, |ips| {
+        ips.retain(|v| v != ip);
+    })
+    .await
 }
 
 #[inline]
@@ -133,3 +534,496 @@ pub(crate) fn record_type_from_ip(ip: &IpAddr) -> &'static str {
         IpAddr::V6(_) => "AAAA",
     }
 }
+
+#[inline]
+pub(crate) fn record_type_from_family(family: IpType) -> &'static str {
+    match family {
+        IpType::V4 => "A",
+        IpType::V6 => "AAAA",
+    }
+}
+
+#[inline]
+pub(crate) fn family_of(ip: &IpAddr) -> IpType {
+    match ip {
+        IpAddr::V4(_) => IpType::V4,
+        IpAddr::V6(_) => IpType::V6,
+    }
+}
+
+#[inline]
+pub(crate) fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    Hmac::<Sha256>::new_from_slice(key)
+        .expect("hmac accepts keys of any length")
+        .chain_update(data)
+        .finalize()
+        .into_bytes()
+        .to_vec()
+}
+
+#[inline]
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Percent encode everything except the RFC 3986 unreserved characters,
+/// as required by most of the request signing schemes
+#[inline]
+pub(crate) fn percent_encode(value: &str) -> String {
+    utf8_percent_encode(value, UNRESERVED).to_string()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+
+    use super::*;
+    use crate::state::tests::state_path;
+    use crate::state::State;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub(crate) struct MockRecord {
+        pub(crate) id: usize,
+        pub(crate) ip: IpAddr,
+    }
+
+    impl AsRef<IpAddr> for MockRecord {
+        fn as_ref(&self) -> &IpAddr {
+            &self.ip
+        }
+    }
+
+    /// Keeps the records in memory and logs every change made to them
+    pub(crate) struct Mock {
+        records: Mutex<Vec<MockRecord>>,
+        next_id: Mutex<usize>,
+        calls: Mutex<Vec<String>>,
+        replace: bool,
+    }
+
+    impl Mock {
+        pub(crate) fn new(ips: &[&str], replace: bool) -> Self {
+            let records = ips
+                .iter()
+                .enumerate()
+                .map(|(id, ip)| MockRecord {
+                    id,
+                    ip: ip.parse().unwrap(),
+                })
+                .collect::<Vec<_>>();
+            Mock {
+                next_id: Mutex::new(records.len()),
+                records: Mutex::new(records),
+                calls: Mutex::new(vec![]),
+                replace,
+            }
+        }
+
+        pub(crate) fn ips(&self) -> Vec<String> {
+            self.records.lock().unwrap().iter().map(|v| v.ip.to_string()).collect()
+        }
+
+        pub(crate) fn calls(&self) -> Vec<String> {
+            self.calls.lock().unwrap().clone()
+        }
+
+        fn log(&self, call: String) {
+            self.calls.lock().unwrap().push(call);
+        }
+
+        fn push(&self, ip: IpAddr) {
+            let mut next_id = self.next_id.lock().unwrap();
+            self.records.lock().unwrap().push(MockRecord {
+                id: *next_id,
+                ip,
+            });
+            *next_id += 1;
+        }
+    }
+
+    #[async_trait]
+    impl Provider for Mock {
+        type DNSRecord = MockRecord;
+
+        async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+            let records = self.records.lock().unwrap();
+            Ok(records.iter().filter(|v| family_of(&v.ip) == family).cloned().collect())
+        }
+
+        async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+            self.log(format!("create {} {}", ip, ttl));
+            self.push(*ip);
+            Ok(())
+        }
+
+        async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+            self.log(format!("update {} {}", record.id, ip));
+            let mut records = self.records.lock().unwrap();
+            let record = records
+                .iter_mut()
+                .find(|v| v.id == record.id)
+                .ok_or_else(|| anyhow!("no record {}", record.id))?;
+            record.ip = *ip;
+            Ok(())
+        }
+
+        async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+            self.log(format!("delete {}", record.id));
+            self.records.lock().unwrap().retain(|v| v.id != record.id);
+            Ok(())
+        }
+
+        fn supports_replace(&self) -> bool {
+            self.replace
+        }
+
+        async fn replace_dns_records(
+            &self,
+            family: IpType,
+            _records: &[Self::DNSRecord],
+            ips: &[IpAddr],
+            ttl: u32,
+        ) -> Result<()> {
+            let ips_str = ips.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",");
+            self.log(format!(
+                "replace {} [{}] {}",
+                record_type_from_family(family),
+                ips_str,
+                ttl
+            ));
+            self.records.lock().unwrap().retain(|v| family_of(&v.ip) != family);
+            for ip in ips {
+                self.push(*ip);
+            }
+            Ok(())
+        }
+
+        async fn commit_changes(&self) -> Result<()> {
+            self.log("commit".to_owned());
+            Ok(())
+        }
+    }
+
+    /// A local http server for the providers with an overridable api url, every request is answered with the
+    /// status and body `respond` gives for its `METHOD target` line and body
+    pub(crate) struct MockServer {
+        pub(crate) url: String,
+        requests: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    impl MockServer {
+        pub(crate) async fn start<F>(respond: F) -> Self
+        where
+            F: Fn(&str, &str) -> (u16, String) + Send + Sync + 'static,
+        {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let requests = Arc::new(Mutex::new(vec![]));
+            let respond = Arc::new(respond);
+            let logged = requests.clone();
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let mut stream = tokio::io::BufReader::new(stream);
+                    let mut line = String::new();
+                    stream.read_line(&mut line).await.unwrap();
+                    let request = line.rsplitn(2, ' ').last().unwrap_or_default().to_owned();
+                    let mut length = 0;
+                    loop {
+                        let mut header = String::new();
+                        stream.read_line(&mut header).await.unwrap();
+                        let header = header.trim();
+                        if header.is_empty() {
+                            break;
+                        }
+                        if let Some((name, value)) = header.split_once(':') {
+                            if name.eq_ignore_ascii_case("content-length") {
+                                length = value.trim().parse().unwrap();
+                            }
+                        }
+                    }
+                    let mut body = vec![0; length];
+                    stream.read_exact(&mut body).await.unwrap();
+                    let body = String::from_utf8(body).unwrap();
+                    let (status, content) = respond(&request, &body);
+                    logged.lock().unwrap().push((request, body));
+                    let response = format!(
+                        "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: \
+                         close\r\n\r\n{}",
+                        status,
+                        content.len(),
+                        content
+                    );
+                    stream.get_mut().write_all(response.as_bytes()).await.unwrap();
+                }
+            });
+            MockServer {
+                url,
+                requests,
+            }
+        }
+
+        /// The `METHOD target` line and the body of every request so far
+        pub(crate) fn requests(&self) -> Vec<(String, String)> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    pub(crate) fn ips(values: &[&str]) -> Vec<IpAddr> {
+        values.iter().map(|v| v.parse().unwrap()).collect()
+    }
+
+    /// An ownership backed by a fresh state file, `owned` is recorded as written by ddns-rs unless `None`
+    pub(crate) async fn ownership(name: &str, owned: Option<&[&str]>, adopt_existing: bool) -> Ownership {
+        let state = Arc::new(State::load(state_path(name)).await.unwrap());
+        let ownership = Ownership::new(state, "p1", adopt_existing, false);
+        if let Some(owned) = owned {
+            ownership.set(IpType::V4, &ips(owned)).await.unwrap();
+        }
+        ownership
+    }
+
+    async fn plan(mock: &Mock, new_ips: &[&str], ownership: &Ownership) -> ChangeSet {
+        let (_, change_set) = make_plan(mock, &ips(new_ips), 600, false, IpType::V4, ownership)
+            .await
+            .unwrap();
+        change_set
+    }
+
+    #[tokio::test]
+    async fn plan_leaves_unmanaged_alone() {
+        let mock = Mock::new(&["192.0.2.1", "192.0.2.2", "2001:db8::1"], false);
+        let ownership = ownership("plan-unmanaged", Some(&["192.0.2.2"]), false).await;
+        let change_set = plan(&mock, &["192.0.2.3"], &ownership).await;
+        assert_eq!(change_set.unmanaged, ips(&["192.0.2.1"]));
+        assert_eq!(change_set.updates, [(ips(&["192.0.2.2"])[0], ips(&["192.0.2.3"])[0])]);
+        assert!(change_set.creates.is_empty());
+        assert!(change_set.deletes.is_empty());
+        assert_eq!(change_set.owned, ips(&["192.0.2.3"]));
+    }
+
+    #[tokio::test]
+    async fn plan_reuses_unmanaged_address() {
+        let mock = Mock::new(&["192.0.2.1", "192.0.2.2"], false);
+        let ownership = ownership("plan-reuse", Some(&["192.0.2.2"]), false).await;
+        let change_set = plan(&mock, &["192.0.2.1"], &ownership).await;
+        assert_eq!(change_set.unmanaged, ips(&["192.0.2.1"]));
+        assert_eq!(change_set.deletes, ips(&["192.0.2.2"]));
+        assert!(change_set.creates.is_empty());
+        assert!(change_set.owned.is_empty());
+    }
+
+    #[tokio::test]
+    async fn plan_adopts_existing() {
+        let mock = Mock::new(&["192.0.2.1", "192.0.2.2"], false);
+        let ownership = ownership("plan-adopt", Some(&[]), true).await;
+        let change_set = plan(&mock, &["192.0.2.2"], &ownership).await;
+        assert!(change_set.unmanaged.is_empty());
+        assert_eq!(change_set.deletes, ips(&["192.0.2.1"]));
+        assert_eq!(change_set.owned, ips(&["192.0.2.2"]));
+    }
+
+    #[tokio::test]
+    async fn plan_first_run() {
+        let mock = Mock::new(&["192.0.2.1"], false);
+        let adopting = ownership("plan-first-run-adopt", None, true).await;
+        let ownership = ownership("plan-first-run", None, false).await;
+        let change_set = plan(&mock, &["192.0.2.3"], &ownership).await;
+        assert_eq!(change_set.unmanaged, ips(&["192.0.2.1"]));
+        assert_eq!(change_set.creates, ips(&["192.0.2.3"]));
+        assert!(change_set.updates.is_empty());
+        // planning never writes the state
+        assert!(ownership.owned(IpType::V4).await.is_none());
+
+        mock.check_and_update(&ips(&["192.0.2.3"]), 600, false, IpType::V4, &ownership)
+            .await
+            .unwrap();
+        assert_eq!(mock.ips(), ["192.0.2.1", "192.0.2.3"]);
+        let owned = ownership.owned(IpType::V4).await.unwrap();
+        assert_eq!(owned, ips(&["192.0.2.3"]).into_iter().collect());
+
+        // migrating takes the existing records over
+        let mock = Mock::new(&["192.0.2.1"], false);
+        mock.check_and_update(&ips(&["192.0.2.3"]), 600, false, IpType::V4, &adopting)
+            .await
+            .unwrap();
+        assert_eq!(mock.calls(), ["update 0 192.0.2.3", "commit"]);
+        let owned = adopting.owned(IpType::V4).await.unwrap();
+        assert_eq!(owned, ips(&["192.0.2.3"]).into_iter().collect());
+    }
+
+    #[tokio::test]
+    async fn plan_pairs_updates() {
+        let mock = Mock::new(&["192.0.2.1", "192.0.2.2"], false);
+        let ownership = ownership("plan-pairs", Some(&["192.0.2.1", "192.0.2.2"]), false).await;
+        let change_set = plan(&mock, &["192.0.2.2", "192.0.2.3", "192.0.2.4"], &ownership).await;
+        assert_eq!(change_set.updates, [(ips(&["192.0.2.1"])[0], ips(&["192.0.2.3"])[0])]);
+        assert_eq!(change_set.creates, ips(&["192.0.2.4"]));
+        assert!(change_set.deletes.is_empty());
+        assert!(change_set.forced.is_empty());
+        assert_eq!(change_set.changed_ips(), ips(&["192.0.2.3", "192.0.2.4"]));
+
+        let mock = Mock::new(&["192.0.2.1", "192.0.2.2"], true);
+        let change_set = plan(&mock, &["192.0.2.2", "192.0.2.3", "192.0.2.4"], &ownership).await;
+        assert!(change_set.replace);
+        assert!(change_set.updates.is_empty());
+        assert_eq!(change_set.creates, ips(&["192.0.2.3", "192.0.2.4"]));
+        assert_eq!(change_set.deletes, ips(&["192.0.2.1"]));
+    }
+
+    #[tokio::test]
+    async fn plan_forced() {
+        let mock = Mock::new(&["192.0.2.1"], false);
+        let ownership = ownership("plan-forced", Some(&["192.0.2.1"]), false).await;
+        let (_, change_set) = make_plan(&mock, &ips(&["192.0.2.1"]), 600, true, IpType::V4, &ownership)
+            .await
+            .unwrap();
+        assert_eq!(change_set.forced, ips(&["192.0.2.1"]));
+        assert!(!change_set.is_empty());
+        assert_eq!(change_set.to_string(), "  ~ A 192.0.2.1 (forced)\n");
+    }
+
+    #[tokio::test]
+    async fn plan_display() {
+        let mock = Mock::new(&["192.0.2.1", "192.0.2.2", "192.0.2.9"], false);
+        let ownership = ownership("plan-display", Some(&["192.0.2.1", "192.0.2.2"]), false).await;
+        let change_set = plan(&mock, &["192.0.2.3"], &ownership).await;
+        assert_eq!(
+            change_set.to_string(),
+            "  ~ A 192.0.2.1 -> 192.0.2.3\n  - A 192.0.2.2\n  = A 192.0.2.9 (unmanaged)\n"
+        );
+        let change_set = plan(&mock, &["192.0.2.1", "192.0.2.2"], &ownership).await;
+        assert_eq!(change_set.to_string(), "  = A 192.0.2.9 (unmanaged)\n  no changes\n");
+    }
+
+    #[tokio::test]
+    async fn apply_per_record() {
+        let mock = Mock::new(&["192.0.2.1", "192.0.2.2", "192.0.2.9", "2001:db8::1"], false);
+        let ownership = ownership("apply-per-record", Some(&["192.0.2.1", "192.0.2.2"]), false).await;
+        let changed = mock
+            .check_and_update(&ips(&["192.0.2.3"]), 600, false, IpType::V4, &ownership)
+            .await
+            .unwrap();
+        assert_eq!(changed, ips(&["192.0.2.3"]));
+        assert_eq!(mock.calls(), ["update 0 192.0.2.3", "delete 1", "commit"]);
+        assert_eq!(mock.ips(), ["192.0.2.3", "192.0.2.9", "2001:db8::1"]);
+        let owned = ownership.owned(IpType::V4).await.unwrap();
+        assert_eq!(owned, ips(&["192.0.2.3"]).into_iter().collect());
+
+        let changed = mock
+            .check_and_update(&ips(&["192.0.2.3", "192.0.2.4"]), 600, false, IpType::V4, &ownership)
+            .await
+            .unwrap();
+        assert_eq!(changed, ips(&["192.0.2.4"]));
+        assert_eq!(mock.calls()[3..], ["create 192.0.2.4 600", "commit"]);
+        let owned = ownership.owned(IpType::V4).await.unwrap();
+        assert_eq!(owned, ips(&["192.0.2.3", "192.0.2.4"]).into_iter().collect());
+    }
+
+    #[tokio::test]
+    async fn apply_replace() {
+        let mock = Mock::new(&["192.0.2.1", "192.0.2.9"], true);
+        let ownership = ownership("apply-replace", Some(&["192.0.2.1"]), false).await;
+        mock.check_and_update(&ips(&["192.0.2.3"]), 600, false, IpType::V4, &ownership)
+            .await
+            .unwrap();
+        // the unmanaged records are written back along with ours
+        assert_eq!(mock.calls(), ["replace A [192.0.2.3,192.0.2.9] 600", "commit"]);
+        let owned = ownership.owned(IpType::V4).await.unwrap();
+        assert_eq!(owned, ips(&["192.0.2.3"]).into_iter().collect());
+    }
+
+    #[tokio::test]
+    async fn apply_nothing() {
+        let mock = Mock::new(&["192.0.2.1"], false);
+        let ownership = ownership("apply-nothing", Some(&["192.0.2.1"]), false).await;
+        let changed = mock
+            .check_and_update(&ips(&["192.0.2.1"]), 600, false, IpType::V4, &ownership)
+            .await
+            .unwrap();
+        assert!(changed.is_empty());
+        assert!(mock.calls().is_empty());
+        let owned = ownership.owned(IpType::V4).await.unwrap();
+        assert_eq!(owned, ips(&["192.0.2.1"]).into_iter().collect());
+    }
+
+    #[tokio::test]
+    async fn apply_stale_plan() {
+        let mock = Mock::new(&["192.0.2.1"], false);
+        let ownership = ownership("apply-stale", Some(&["192.0.2.1"]), false).await;
+        let (_, change_set) = make_plan(&mock, &ips(&["192.0.2.3"]), 600, false, IpType::V4, &ownership)
+            .await
+            .unwrap();
+        // planned against records that are gone
+        assert!(apply_plan(&mock, &[], &change_set, &ownership).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn record_set_rewrite() {
+        let mock = Mock::new(&["192.0.2.1", "192.0.2.2"], true);
+        let ip = ips(&["192.0.2.3"])[0];
+        modify_record_set(&mock, IpType::V4, 600, |ips| ips.push(ip))
+            .await
+            .unwrap();
+        assert_eq!(mock.calls(), ["replace A [192.0.2.1,192.0.2.2,192.0.2.3] 600"]);
+
+        let mock = Mock::new(&["192.0.2.1"], false);
+        assert!(modify_record_set(&mock, IpType::V4, 600, |_| {}).await.is_err());
+        assert!(mock.calls().is_empty());
+    }
+
+    #[test]
+    fn zones() {
+        assert_eq!(zone_of("www.example.co.uk", None).unwrap(), "example.co.uk");
+        assert_eq!(
+            zone_of("www.example.com", Some("www.example.com.")).unwrap(),
+            "www.example.com"
+        );
+        assert!(zone_of("localhost", None).is_err());
+        let domains = [(1, "example.com".to_owned()), (2, "sub.example.com.".to_owned())];
+        assert_eq!(
+            find_domain_id("a.sub.example.com", &domains).unwrap(),
+            (2, "sub.example.com".to_owned())
+        );
+        assert_eq!(
+            find_domain_id("example.com.", &domains).unwrap(),
+            (1, "example.com".to_owned())
+        );
+        assert!(find_domain_id("fooexample.com", &domains).is_err());
+    }
+
+    #[test]
+    fn relative_names() {
+        assert_eq!(relative_name("www.example.com", "example.com").unwrap(), "www");
+        assert_eq!(relative_name("example.com.", "example.com").unwrap(), "");
+        assert_eq!(relative_name("a.b.example.com", "example.com.").unwrap(), "a.b");
+        // the suffix is only stripped once
+        assert_eq!(
+            relative_name("example.com.example.com", "example.com").unwrap(),
+            "example.com"
+        );
+        // a partial label doesn't match
+        assert!(relative_name("fooexample.com", "example.com").is_err());
+        assert!(relative_name("www.example.org", "example.com").is_err());
+    }
+
+    #[tokio::test]
+    async fn record_set_helpers() {
+        let mock = Mock::new(&["192.0.2.1", "192.0.2.2"], true);
+        let [old, new] = [ips(&["192.0.2.1"])[0], ips(&["192.0.2.3"])[0]];
+        create_in_record_set(&mock, &new, 600).await.unwrap();
+        create_in_record_set(&mock, &new, 600).await.unwrap();
+        update_in_record_set(&mock, &old, &new, 60).await.unwrap();
+        delete_from_record_set(&mock, &new, 60).await.unwrap();
+        assert_eq!(
+            mock.calls(),
+            [
+                "replace A [192.0.2.1,192.0.2.2,192.0.2.3] 600",
+                "replace A [192.0.2.1,192.0.2.2,192.0.2.3] 600",
+                "replace A [192.0.2.2,192.0.2.3] 60",
+                "replace A [192.0.2.2] 60",
+            ]
+        );
+    }
+}
@@ -0,0 +1,388 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+
+use addr::parse_dns_name;
+use anyhow::{anyhow, bail, ensure, Result};
+use async_trait::async_trait;
+use log::debug;
+use reqwest::Client;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use super::{
+    create_in_record_set,
+    delete_from_record_set,
+    lookup_dns_record,
+    record_type_from_family,
+    relative_name,
+    update_in_record_set,
+    Provider,
+};
+use crate::IpType;
+
+const DEFAULT_API_URL: &str = "https://api.namecheap.com/xml.response";
+const DEFAULT_DYNAMIC_URL: &str = "https://dynamicdns.park-your-domain.com/update";
+/// The TTL namecheap shows as "Automatic"
+const AUTOMATIC_TTL: u32 = 1799;
+
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    /// Only known in api mode
+    pub host_id: Option<String>,
+    pub ip: IpAddr,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.host_id {
+            Some(host_id) => write!(f, "{}", host_id),
+            None => write!(f, "{}", self.ip),
+        }
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+pub enum Mode {
+    /// The dynamic dns password of the domain, only the A record can be set
+    Dynamic { password: String },
+    /// The full xml api, `setHosts` replaces every host of the domain
+    Api {
+        api_user: String,
+        api_key: String,
+        username: String,
+        client_ip: String,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiResponse {
+    #[serde(rename = "@Status")]
+    status: String,
+    #[serde(rename = "Errors", default)]
+    errors: ApiErrors,
+    #[serde(rename = "CommandResponse")]
+    command_response: Option<CommandResponse>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct ApiErrors {
+    #[serde(rename = "Error", default)]
+    items: Vec<ApiError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiError {
+    #[serde(rename = "@Number")]
+    number: String,
+    #[serde(rename = "$text", default)]
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct CommandResponse {
+    #[serde(rename = "DomainDNSGetHostsResult")]
+    hosts_result: Option<HostsResult>,
+}
+
+#[derive(Deserialize, Debug)]
+struct HostsResult {
+    #[serde(rename = "@EmailType")]
+    email_type: Option<String>,
+    #[serde(rename = "host", default)]
+    hosts: Vec<Host>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct Host {
+    #[serde(rename = "@HostId")]
+    id: String,
+    #[serde(rename = "@Name")]
+    name: String,
+    #[serde(rename = "@Type")]
+    kind: String,
+    #[serde(rename = "@Address")]
+    address: String,
+    #[serde(rename = "@MXPref", default)]
+    mx_pref: String,
+    #[serde(rename = "@TTL", default)]
+    ttl: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct DynamicResponse {
+    #[serde(rename = "ErrCount")]
+    err_count: u32,
+    #[serde(rename = "errors", default)]
+    errors: DynamicErrors,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct DynamicErrors {
+    #[serde(rename = "Err1")]
+    err1: Option<String>,
+}
+
+pub struct Namecheap {
+    dns: String,
+    sld: String,
+    tld: String,
+    host: String,
+    mode: Mode,
+    api_url: String,
+    dynamic_url: String,
+    client: Client,
+    /// The IP last set in dynamic mode, which can't be read back from namecheap
+    last_ip: Mutex<Option<IpAddr>>,
+}
+
+impl Namecheap {
+    pub async fn create<D: AsRef<str>>(
+        dns: D,
+        mode: Mode,
+        api_url: Option<&str>,
+        dynamic_url: Option<&str>,
+    ) -> Result<Self> {
+        let dns = dns.as_ref().trim_end_matches('.').to_owned();
+
+        let result = parse_dns_name(&dns).map_err(|err| anyhow!("can't parse dns {}: {}", dns, err))?;
+        let domain = result.root().ok_or_else(|| anyhow!("can't find domain of {}", dns))?;
+        let tld = result.suffix().ok_or_else(|| anyhow!("can't find tld of {}", dns))?;
+        let sld = relative_name(domain, tld)?;
+        let host = relative_name(&dns, domain)?;
+        let host = if host.is_empty() { "@".to_owned() } else { host };
+
+        Ok(Namecheap {
+            sld,
+            tld: tld.to_owned(),
+            host,
+            mode,
+            api_url: api_url.unwrap_or(DEFAULT_API_URL).to_owned(),
+            dynamic_url: dynamic_url.unwrap_or(DEFAULT_DYNAMIC_URL).to_owned(),
+            client: Client::new(),
+            last_ip: Mutex::new(None),
+            dns,
+        })
+    }
+
+    async fn set_dynamic(&self, password: &str, ip: &IpAddr) -> Result<()> {
+        ensure!(ip.is_ipv4(), "namecheap dynamic dns only supports ipv4");
+        let text = self
+            .client
+            .get(&self.dynamic_url)
+            .query(&[
+                ("host", &*self.host),
+                ("domain", &format!("{}.{}", self.sld, self.tld)),
+                ("password", password),
+                ("ip", &ip.to_string()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let response = quick_xml::de::from_str::<DynamicResponse>(&text)?;
+        if response.err_count > 0 {
+            bail!(
+                "namecheap error: {}",
+                response.errors.err1.as_deref().unwrap_or("unknown error")
+            );
+        }
+        *self.last_ip.lock().await = Some(*ip);
+        Ok(())
+    }
+
+    async fn call_api(&self, command: &str, params: &[(String, String)]) -> Result<ApiResponse> {
+        let (api_user, api_key, username, client_ip) = match &self.mode {
+            Mode::Api {
+                api_user,
+                api_key,
+                username,
+                client_ip,
+            } => (api_user, api_key, username, client_ip),
+            Mode::Dynamic {
+                ..
+            } => bail!("namecheap xml api is not configured"),
+        };
+        let mut form = vec![
+            ("ApiUser".to_owned(), api_user.clone()),
+            ("ApiKey".to_owned(), api_key.clone()),
+            ("UserName".to_owned(), username.clone()),
+            ("ClientIp".to_owned(), client_ip.clone()),
+            ("Command".to_owned(), command.to_owned()),
+            ("SLD".to_owned(), self.sld.clone()),
+            ("TLD".to_owned(), self.tld.clone()),
+        ];
+        form.extend_from_slice(params);
+        let text = self
+            .client
+            .post(&self.api_url)
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let response = quick_xml::de::from_str::<ApiResponse>(&text)?;
+        if response.status != "OK" {
+            let errors = response
+                .errors
+                .items
+                .iter()
+                .map(|v| format!("{} {}", v.number, v.message.trim()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            bail!("namecheap error: {}", errors);
+        }
+        Ok(response)
+    }
+
+    async fn get_hosts(&self) -> Result<HostsResult> {
+        self.call_api("namecheap.domains.dns.getHosts", &[])
+            .await?
+            .command_response
+            .and_then(|v| v.hosts_result)
+            .ok_or_else(|| anyhow!("namecheap error: missing getHosts result"))
+    }
+
+    /// Write back every host of the domain, replacing only the records of `kind` on our host
+    async fn set_hosts(&self, kind: &str, ips: &[IpAddr], ttl: u32) -> Result<()> {
+        let current = self.get_hosts().await?;
+        let mut hosts = vec![];
+        let mut record_ttl = None;
+        for host in current.hosts {
+            if host.name.eq_ignore_ascii_case(&self.host) && host.kind == kind {
+                record_ttl = record_ttl.or_else(|| host.ttl.parse::<u32>().ok());
+                continue;
+            }
+            hosts.push(host);
+        }
+        let ttl = record_ttl.unwrap_or(ttl).to_string();
+        for ip in ips {
+            hosts.push(Host {
+                id: String::new(),
+                name: self.host.clone(),
+                kind: kind.to_owned(),
+                address: ip.to_string(),
+                mx_pref: String::new(),
+                ttl: ttl.clone(),
+            });
+        }
+
+        let mut params = vec![];
+        if let Some(email_type) = current.email_type {
+            params.push(("EmailType".to_owned(), email_type));
+        }
+        for (i, host) in hosts.into_iter().enumerate() {
+            let i = i + 1;
+            params.push((format!("HostName{}", i), host.name));
+            params.push((format!("RecordType{}", i), host.kind));
+            params.push((format!("Address{}", i), host.address));
+            if !host.mx_pref.is_empty() {
+                params.push((format!("MXPref{}", i), host.mx_pref));
+            }
+            if !host.ttl.is_empty() {
+                params.push((format!("TTL{}", i), host.ttl));
+            }
+        }
+        debug!("writing back {} namecheap host param(s)", params.len());
+        self.call_api("namecheap.domains.dns.setHosts", &params).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Provider for Namecheap {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        let kind = record_type_from_family(family);
+        match &self.mode {
+            Mode::Dynamic {
+                ..
+            } => {
+                ensure!(family == IpType::V4, "namecheap dynamic dns only supports ipv4");
+                let ips = match *self.last_ip.lock().await {
+                    Some(ip) => vec![ip],
+                    None => lookup_dns_record(&self.dns, family).await?,
+                };
+                Ok(ips
+                    .into_iter()
+                    .map(|ip| DNSRecord {
+                        host_id: None,
+                        ip,
+                    })
+                    .collect())
+            },
+            Mode::Api {
+                ..
+            } => {
+                let mut records = vec![];
+                for host in self.get_hosts().await?.hosts {
+                    if host.name.eq_ignore_ascii_case(&self.host) && host.kind == kind {
+                        records.push(DNSRecord {
+                            host_id: Some(host.id),
+                            ip: host.address.parse()?,
+                        });
+                    }
+                }
+                Ok(records)
+            },
+        }
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        match &self.mode {
+            Mode::Dynamic {
+                password,
+            } => self.set_dynamic(password, ip).await,
+            Mode::Api {
+                ..
+            } => create_in_record_set(self, ip, ttl).await,
+        }
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        match &self.mode {
+            Mode::Dynamic {
+                password,
+            } => self.set_dynamic(password, ip).await,
+            Mode::Api {
+                ..
+            } => update_in_record_set(self, &record.ip, ip, AUTOMATIC_TTL).await,
+        }
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        match &self.mode {
+            Mode::Dynamic {
+                ..
+            } => bail!(
+                "namecheap dynamic dns can't delete record {}, remove it manually",
+                record.ip
+            ),
+            Mode::Api {
+                ..
+            } => delete_from_record_set(self, &record.ip, AUTOMATIC_TTL).await,
+        }
+    }
+
+    fn supports_replace(&self) -> bool {
+        matches!(self.mode, Mode::Api { .. })
+    }
+
+    async fn replace_dns_records(
+        &self,
+        family: IpType,
+        _records: &[Self::DNSRecord],
+        ips: &[IpAddr],
+        ttl: u32,
+    ) -> Result<()> {
+        self.set_hosts(record_type_from_family(family), ips, ttl).await
+    }
+}
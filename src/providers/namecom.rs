@@ -0,0 +1,186 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{record_type_from_ip, relative_name, zone_of, Provider};
+use crate::IpType;
+
+const DEFAULT_API_URL: &str = "https://api.name.com/v4";
+const PER_PAGE: u32 = 1000;
+
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub id: u64,
+    pub ip: IpAddr,
+    pub ttl: u32,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorResponse {
+    message: String,
+    details: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ListRecordsResponse {
+    #[serde(default)]
+    records: Vec<Record>,
+    #[serde(rename = "nextPage")]
+    next_page: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Record {
+    id: u64,
+    #[serde(default)]
+    host: String,
+    #[serde(rename = "type")]
+    kind: String,
+    answer: String,
+    ttl: u32,
+}
+
+pub struct NameCom {
+    domain: String,
+    host: String,
+    api_url: String,
+    username: String,
+    token: String,
+    client: Client,
+}
+
+impl NameCom {
+    pub async fn create<U: AsRef<str>, T: AsRef<str>, D: AsRef<str>>(
+        username: U,
+        token: T,
+        dns: D,
+        domain: Option<&str>,
+        api_url: Option<&str>,
+    ) -> Result<Self> {
+        let dns = dns.as_ref().trim_end_matches('.').to_owned();
+
+        let domain = zone_of(&dns, domain)?;
+
+        Ok(NameCom {
+            host: relative_name(&dns, &domain)?,
+            domain,
+            api_url: api_url.unwrap_or(DEFAULT_API_URL).trim_end_matches('/').to_owned(),
+            username: username.as_ref().to_owned(),
+            token: token.as_ref().to_owned(),
+            client: Client::new(),
+        })
+    }
+
+    async fn send<T: DeserializeOwned>(&self, builder: RequestBuilder) -> Result<T> {
+        let response = builder.basic_auth(&self.username, Some(&self.token)).send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            match serde_json::from_str::<ErrorResponse>(&text) {
+                Ok(ErrorResponse {
+                    message,
+                    details: Some(details),
+                }) => bail!("name.com error {}: {} ({})", status, message, details),
+                Ok(err) => bail!("name.com error {}: {}", status, err.message),
+                Err(_) => bail!("name.com error {}: {}", status, text),
+            }
+        }
+        if text.is_empty() {
+            return Ok(serde_json::from_value(Value::Null)?);
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+#[async_trait]
+impl Provider for NameCom {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        let url = format!("{}/domains/{}/records", self.api_url, self.domain);
+        let mut result = vec![];
+        let mut current_page = 1;
+        loop {
+            let response: ListRecordsResponse = self
+                .send(
+                    self.client
+                        .get(&url)
+                        .query(&[("page", current_page), ("perPage", PER_PAGE)]),
+                )
+                .await?;
+
+            for record in &response.records {
+                if !record.host.eq_ignore_ascii_case(&self.host) {
+                    continue;
+                }
+                match (family, &*record.kind) {
+                    (IpType::V6, "AAAA") | (IpType::V4, "A") => {
+                        result.push(DNSRecord {
+                            id: record.id,
+                            ip: record.answer.parse()?,
+                            ttl: record.ttl,
+                        });
+                    },
+                    _ => {},
+                }
+            }
+
+            match response.next_page {
+                Some(next_page) if next_page > current_page => current_page = next_page,
+                _ => break,
+            }
+        }
+        Ok(result)
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        let url = format!("{}/domains/{}/records", self.api_url, self.domain);
+        let _: Value = self
+            .send(self.client.post(url).json(&json!({
+                "host": self.host,
+                "type": record_type_from_ip(ip),
+                "answer": ip.to_string(),
+                "ttl": ttl,
+            })))
+            .await?;
+        Ok(())
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        let url = format!("{}/domains/{}/records/{}", self.api_url, self.domain, record.id);
+        let _: Value = self
+            .send(self.client.put(url).json(&json!({
+                "host": self.host,
+                "type": record_type_from_ip(ip),
+                "answer": ip.to_string(),
+                "ttl": record.ttl,
+            })))
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        let url = format!("{}/domains/{}/records/{}", self.api_url, self.domain, record.id);
+        let _: Value = self.send(self.client.delete(url)).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+
+use super::{record_type_from_ip, relative_name, zone_of, Provider};
+use crate::IpType;
+
+const DEFAULT_API_URL: &str = "https://www.namesilo.com/api";
+/// Reply codes that mean the operation succeeded
+const SUCCESS_CODES: [&str; 3] = ["300", "301", "302"];
+
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub id: String,
+    pub ip: IpAddr,
+    pub ttl: u32,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct Response {
+    reply: Reply,
+}
+
+#[derive(Deserialize, Debug)]
+struct Reply {
+    code: String,
+    #[serde(default)]
+    detail: String,
+    #[serde(default)]
+    resource_record: Vec<ResourceRecord>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ResourceRecord {
+    record_id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    host: String,
+    value: String,
+    ttl: u32,
+}
+
+pub struct NameSilo {
+    dns: String,
+    domain: String,
+    host: String,
+    api_url: String,
+    api_key: String,
+    client: Client,
+}
+
+impl NameSilo {
+    pub async fn create<K: AsRef<str>, D: AsRef<str>>(
+        api_key: K,
+        dns: D,
+        domain: Option<&str>,
+        api_url: Option<&str>,
+    ) -> Result<Self> {
+        let dns = dns.as_ref().trim_end_matches('.').to_owned();
+
+        let domain = zone_of(&dns, domain)?;
+
+        Ok(NameSilo {
+            host: relative_name(&dns, &domain)?,
+            dns,
+            domain,
+            api_url: api_url.unwrap_or(DEFAULT_API_URL).trim_end_matches('/').to_owned(),
+            api_key: api_key.as_ref().to_owned(),
+            client: Client::new(),
+        })
+    }
+
+    async fn call(&self, operation: &str, params: &[(&str, String)]) -> Result<Reply> {
+        let url = format!("{}/{}", self.api_url, operation);
+        let mut query = vec![
+            ("version", "1".to_owned()),
+            ("type", "xml".to_owned()),
+            ("key", self.api_key.clone()),
+            ("domain", self.domain.clone()),
+        ];
+        query.extend_from_slice(params);
+        // the api key is in the url, so don't let reqwest put it into the error
+        let response = self
+            .client
+            .get(url)
+            .query(&query)
+            .send()
+            .await
+            .map_err(|err| err.without_url())?;
+        let status = response.status();
+        let text = response.text().await.map_err(|err| err.without_url())?;
+        if !status.is_success() {
+            bail!("namesilo error {}: {}", status, text);
+        }
+        let reply = quick_xml::de::from_str::<Response>(&text)
+            .map_err(|err| anyhow!("namesilo error: unexpected response {}: {}", err, text))?
+            .reply;
+        if !SUCCESS_CODES.contains(&reply.code.as_str()) {
+            bail!("namesilo error {}: {}", reply.code, reply.detail);
+        }
+        Ok(reply)
+    }
+}
+
+#[async_trait]
+impl Provider for NameSilo {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        let mut result = vec![];
+        for record in self.call("dnsListRecords", &[]).await?.resource_record {
+            if !record.host.eq_ignore_ascii_case(&self.dns) {
+                continue;
+            }
+            match (family, &*record.kind) {
+                (IpType::V6, "AAAA") | (IpType::V4, "A") => {
+                    result.push(DNSRecord {
+                        id: record.record_id,
+                        ip: record.value.parse()?,
+                        ttl: record.ttl,
+                    });
+                },
+                _ => {},
+            }
+        }
+        Ok(result)
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        self.call(
+            "dnsAddRecord",
+            &[
+                ("rrtype", record_type_from_ip(ip).to_owned()),
+                ("rrhost", self.host.clone()),
+                ("rrvalue", ip.to_string()),
+                ("rrttl", ttl.to_string()),
+            ],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        self.call(
+            "dnsUpdateRecord",
+            &[
+                ("rrid", record.id.clone()),
+                ("rrhost", self.host.clone()),
+                ("rrvalue", ip.to_string()),
+                ("rrttl", record.ttl.to_string()),
+            ],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        self.call("dnsDeleteRecord", &[("rrid", record.id.clone())]).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn keep_api_key_out_of_errors() {
+        // nothing listens on the discard port
+        let provider = NameSilo::create("secret-key", "home.example.com", None, Some("http://127.0.0.1:9"))
+            .await
+            .unwrap();
+        let err = provider.get_dns_record(IpType::V4).await.unwrap_err();
+        assert!(!format!("{:?}", err).contains("secret-key"));
+    }
+}
@@ -0,0 +1,326 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use log::debug;
+use reqwest::{Client, Method};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+use sha1::{Digest, Sha1};
+
+use super::{percent_encode, record_type_from_ip, relative_name, zone_of, Provider};
+use crate::IpType;
+
+const DEFAULT_ENDPOINT: &str = "ovh-eu";
+
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub id: u64,
+    pub ip: IpAddr,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorResponse {
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Record {
+    id: u64,
+    target: String,
+}
+
+pub struct Credentials {
+    pub application_key: String,
+    pub application_secret: String,
+    pub consumer_key: String,
+}
+
+pub struct Ovh {
+    zone: String,
+    sub_domain: String,
+    api_url: String,
+    credentials: Credentials,
+    client: Client,
+    /// Seconds to add to the local clock to get the time of the api server
+    time_delta: i64,
+    /// Set from the first change until the zone is refreshed, a refresh that failed is retried on the next run
+    refresh_pending: AtomicBool,
+}
+
+/// Map an endpoint name to its api url, anything else is taken as an url
+fn endpoint_url(endpoint: &str) -> &str {
+    match endpoint {
+        "ovh-eu" => "https://eu.api.ovh.com/1.0",
+        "ovh-ca" => "https://ca.api.ovh.com/1.0",
+        "ovh-us" => "https://api.us.ovhcloud.com/1.0",
+        url => url.trim_end_matches('/'),
+    }
+}
+
+fn unix_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|v| v.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+impl Ovh {
+    pub async fn create<D: AsRef<str>>(
+        credentials: Credentials,
+        dns: D,
+        zone: Option<&str>,
+        endpoint: Option<&str>,
+    ) -> Result<Self> {
+        let dns = dns.as_ref().trim_end_matches('.').to_owned();
+
+        let zone = zone_of(&dns, zone)?;
+
+        let mut provider = Ovh {
+            sub_domain: relative_name(&dns, &zone)?,
+            zone,
+            api_url: endpoint_url(endpoint.unwrap_or(DEFAULT_ENDPOINT)).to_owned(),
+            credentials,
+            client: Client::new(),
+            time_delta: 0,
+            refresh_pending: AtomicBool::new(false),
+        };
+
+        let url = format!("{}/auth/time", provider.api_url);
+        let server_time: i64 = provider
+            .client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        provider.time_delta = server_time - unix_timestamp();
+        debug!("ovh time delta is {}s", provider.time_delta);
+
+        Ok(provider)
+    }
+
+    async fn send<T: DeserializeOwned>(&self, method: Method, path: &str, body: Option<&Value>) -> Result<T> {
+        let url = format!("{}{}", self.api_url, path);
+        let body = body.map(|v| v.to_string()).unwrap_or_default();
+        let timestamp = (unix_timestamp() + self.time_delta).to_string();
+        let signature = format!(
+            "$1${}",
+            hex::encode(Sha1::digest(
+                [
+                    self.credentials.application_secret.as_str(),
+                    &self.credentials.consumer_key,
+                    method.as_str(),
+                    &url,
+                    &body,
+                    &timestamp,
+                ]
+                .join("+")
+            ))
+        );
+
+        let mut builder = self
+            .client
+            .request(method, &url)
+            .header("X-Ovh-Application", &self.credentials.application_key)
+            .header("X-Ovh-Consumer", &self.credentials.consumer_key)
+            .header("X-Ovh-Timestamp", timestamp)
+            .header("X-Ovh-Signature", signature);
+        if !body.is_empty() {
+            builder = builder.header("Content-Type", "application/json").body(body);
+        }
+
+        let response = builder.send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            match serde_json::from_str::<ErrorResponse>(&text) {
+                Ok(err) => bail!("ovh error {}: {}", status, err.message),
+                Err(_) => bail!("ovh error {}: {}", status, text),
+            }
+        }
+        if text.is_empty() {
+            return Ok(serde_json::from_value(Value::Null)?);
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Send a change of the records, the zone has to be refreshed after it even if it fails,
+    /// as the changes sent before may have been written
+    async fn change(&self, method: Method, path: &str, body: Option<&Value>) -> Result<()> {
+        self.refresh_pending.store(true, Ordering::SeqCst);
+        let _: Value = self.send(method, path, body).await?;
+        Ok(())
+    }
+
+    async fn refresh(&self) -> Result<()> {
+        let path = format!("/domain/zone/{}/refresh", self.zone);
+        let _: Value = self.send(Method::POST, &path, None).await?;
+        self.refresh_pending.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Provider for Ovh {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        // the last run may have failed between a change and the refresh, and it wouldn't see anything
+        // left to change now
+        if self.refresh_pending.load(Ordering::SeqCst) {
+            debug!("retrying the refresh of zone {}", self.zone);
+            self.refresh().await?;
+        }
+        let kind = match family {
+            IpType::V4 => "A",
+            IpType::V6 => "AAAA",
+        };
+        let path = format!(
+            "/domain/zone/{}/record?fieldType={}&subDomain={}",
+            self.zone,
+            kind,
+            percent_encode(&self.sub_domain)
+        );
+        let ids: Vec<u64> = self.send(Method::GET, &path, None).await?;
+        let mut result = vec![];
+        for id in ids {
+            let path = format!("/domain/zone/{}/record/{}", self.zone, id);
+            let record: Record = self.send(Method::GET, &path, None).await?;
+            result.push(DNSRecord {
+                id: record.id,
+                ip: record.target.parse()?,
+            });
+        }
+        Ok(result)
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        let path = format!("/domain/zone/{}/record", self.zone);
+        let json = json!({
+            "fieldType": record_type_from_ip(ip),
+            "subDomain": self.sub_domain,
+            "target": ip.to_string(),
+            "ttl": ttl,
+        });
+        self.change(Method::POST, &path, Some(&json)).await
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        let path = format!("/domain/zone/{}/record/{}", self.zone, record.id);
+        let json = json!({
+            "target": ip.to_string(),
+        });
+        self.change(Method::PUT, &path, Some(&json)).await
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        let path = format!("/domain/zone/{}/record/{}", self.zone, record.id);
+        self.change(Method::DELETE, &path, None).await
+    }
+
+    async fn commit_changes(&self) -> Result<()> {
+        self.refresh().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::providers::tests::{ips, MockServer};
+
+    /// Fails the first `refresh_failures` refreshes
+    async fn server(refresh_failures: usize) -> MockServer {
+        let refreshes = Arc::new(AtomicUsize::new(0));
+        MockServer::start(move |request, _| match request {
+            "GET /auth/time" => (200, unix_timestamp().to_string()),
+            "GET /domain/zone/example.com/record?fieldType=A&subDomain=home" => (200, "[]".to_owned()),
+            "POST /domain/zone/example.com/refresh" if refreshes.fetch_add(1, Ordering::SeqCst) < refresh_failures => {
+                (500, r#"{"message": "internal error"}"#.to_owned())
+            },
+            _ => (200, "null".to_owned()),
+        })
+        .await
+    }
+
+    async fn ovh(server: &MockServer) -> Ovh {
+        let credentials = Credentials {
+            application_key: "ak".to_owned(),
+            application_secret: "as".to_owned(),
+            consumer_key: "ck".to_owned(),
+        };
+        Ovh::create(credentials, "home.example.com", None, Some(&server.url))
+            .await
+            .unwrap()
+    }
+
+    fn refreshes(server: &MockServer) -> usize {
+        server
+            .requests()
+            .iter()
+            .filter(|(request, _)| request == "POST /domain/zone/example.com/refresh")
+            .count()
+    }
+
+    #[tokio::test]
+    async fn refresh_after_changes() {
+        let server = server(0).await;
+        let provider = ovh(&server).await;
+        provider.get_dns_record(IpType::V4).await.unwrap();
+        provider.create_dns_record(&ips(&["192.0.2.1"])[0], 60).await.unwrap();
+        provider.commit_changes().await.unwrap();
+        provider.get_dns_record(IpType::V4).await.unwrap();
+        assert_eq!(refreshes(&server), 1);
+    }
+
+    #[tokio::test]
+    async fn retry_failed_refresh() {
+        let server = server(1).await;
+        let provider = ovh(&server).await;
+        provider.create_dns_record(&ips(&["192.0.2.1"])[0], 60).await.unwrap();
+        assert!(provider.commit_changes().await.is_err());
+        // retried before the records are read again, even with nothing left to change
+        provider.get_dns_record(IpType::V4).await.unwrap();
+        provider.get_dns_record(IpType::V4).await.unwrap();
+        assert_eq!(refreshes(&server), 2);
+    }
+
+    #[tokio::test]
+    async fn refresh_after_failed_change() {
+        let server = MockServer::start(|request, _| match request {
+            "GET /auth/time" => (200, unix_timestamp().to_string()),
+            "DELETE /domain/zone/example.com/record/2" => (500, r#"{"message": "internal error"}"#.to_owned()),
+            _ => (200, "[]".to_owned()),
+        })
+        .await;
+        let provider = ovh(&server).await;
+        let record = DNSRecord {
+            id: 2,
+            ip: ips(&["192.0.2.1"])[0],
+        };
+        assert!(provider.delete_dns_record(&record).await.is_err());
+        provider.get_dns_record(IpType::V4).await.unwrap();
+        assert_eq!(refreshes(&server), 1);
+    }
+}
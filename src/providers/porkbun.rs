@@ -0,0 +1,279 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{record_type_from_ip, relative_name, zone_of, Provider};
+use crate::IpType;
+
+const DEFAULT_API_URL: &str = "https://api.porkbun.com/api/json/v3";
+
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub id: String,
+    pub ip: IpAddr,
+    pub ttl: u32,
+    /// Whether this is the only record of its type on the name
+    pub unique: bool,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+pub struct Credentials {
+    pub api_key: String,
+    pub secret_api_key: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Response {
+    status: String,
+    message: Option<String>,
+    #[serde(default)]
+    records: Vec<Record>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Record {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    content: String,
+    ttl: String,
+}
+
+pub struct Porkbun {
+    domain: String,
+    name: String,
+    api_url: String,
+    client: Client,
+    cred: Credentials,
+}
+
+impl Porkbun {
+    pub async fn create<D: AsRef<str>>(
+        dns: D,
+        domain: Option<&str>,
+        api_url: Option<&str>,
+        cred: Credentials,
+    ) -> Result<Self> {
+        let dns = dns.as_ref().trim_end_matches('.').to_owned();
+
+        let domain = zone_of(&dns, domain)?;
+        let name = relative_name(&dns, &domain)?;
+
+        Ok(Porkbun {
+            domain,
+            name,
+            api_url: api_url.unwrap_or(DEFAULT_API_URL).trim_end_matches('/').to_owned(),
+            client: Client::new(),
+            cred,
+        })
+    }
+
+    async fn request(&self, path: String, mut json: Value) -> Result<Response> {
+        json["apikey"] = json!(self.cred.api_key);
+        json["secretapikey"] = json!(self.cred.secret_api_key);
+        let url = format!("{}{}", self.api_url, path);
+        let response = self.client.post(url).json(&json).send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        let response = match serde_json::from_str::<Response>(&text) {
+            Ok(v) => v,
+            Err(_) => bail!("porkbun error {}: {}", status, text),
+        };
+        if response.status != "SUCCESS" {
+            bail!(
+                "porkbun error {}: {}",
+                response.status,
+                response.message.as_deref().unwrap_or("unknown error")
+            );
+        }
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl Provider for Porkbun {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        let kind = match family {
+            IpType::V4 => "A",
+            IpType::V6 => "AAAA",
+        };
+        let path = format!("/dns/retrieveByNameType/{}/{}/{}", self.domain, kind, self.name);
+        let response = self.request(path, json!({})).await?;
+        let found = response.records.iter().filter(|v| v.kind == kind).collect::<Vec<_>>();
+        let mut records = vec![];
+        for record in &found {
+            records.push(DNSRecord {
+                id: record.id.clone(),
+                ip: record.content.parse()?,
+                ttl: record.ttl.parse()?,
+                unique: found.len() == 1,
+            });
+        }
+        Ok(records)
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        let path = format!("/dns/create/{}", self.domain);
+        self.request(
+            path,
+            json!({
+                "name": self.name,
+                "type": record_type_from_ip(ip),
+                "content": ip.to_string(),
+                "ttl": ttl.to_string(),
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        // editByNameType rewrites every record of the name and type,
+        // so it's only safe when there is no other record to clobber
+        let path = if record.unique {
+            format!(
+                "/dns/editByNameType/{}/{}/{}",
+                self.domain,
+                record_type_from_ip(ip),
+                self.name
+            )
+        } else {
+            format!("/dns/edit/{}/{}", self.domain, record.id)
+        };
+        self.request(
+            path,
+            json!({
+                "name": self.name,
+                "type": record_type_from_ip(ip),
+                "content": ip.to_string(),
+                "ttl": record.ttl.to_string(),
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        let path = format!("/dns/delete/{}/{}", self.domain, record.id);
+        self.request(path, json!({})).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::tests::{ips, MockServer};
+
+    const RECORDS: &str = r#"{"status": "SUCCESS", "records": [
+        {"id": "1", "name": "home.example.com", "type": "A", "content": "192.0.2.1", "ttl": "600"},
+        {"id": "2", "name": "home.example.com", "type": "A", "content": "192.0.2.2", "ttl": "600"},
+        {"id": "3", "name": "home.example.com", "type": "TXT", "content": "hello", "ttl": "600"}
+    ]}"#;
+
+    async fn porkbun(server: &MockServer) -> Porkbun {
+        let cred = Credentials {
+            api_key: "pk".to_owned(),
+            secret_api_key: "sk".to_owned(),
+        };
+        Porkbun::create("home.example.com", None, Some(&server.url), cred)
+            .await
+            .unwrap()
+    }
+
+    /// Answers the record list with `records` but only keeps those of the type asked for, as porkbun does
+    async fn server(records: &'static str) -> MockServer {
+        MockServer::start(move |request, _| {
+            if !request.starts_with("POST /dns/retrieveByNameType/") {
+                return (200, r#"{"status": "SUCCESS"}"#.to_owned());
+            }
+            let kind = request.split('/').nth(4).unwrap().to_owned();
+            let mut response: Value = serde_json::from_str(records).unwrap();
+            response["records"]
+                .as_array_mut()
+                .unwrap()
+                .retain(|v| v["type"] == kind || v["type"] == "TXT");
+            (200, response.to_string())
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn retrieve_records() {
+        let server = server(RECORDS).await;
+        let provider = porkbun(&server).await;
+        let records = provider.get_dns_record(IpType::V4).await.unwrap();
+        let found = records
+            .iter()
+            .map(|v| (v.id.as_str(), v.ip, v.ttl, v.unique))
+            .collect::<Vec<_>>();
+        let ips = ips(&["192.0.2.1", "192.0.2.2"]);
+        assert_eq!(found, [("1", ips[0], 600, false), ("2", ips[1], 600, false)]);
+        let (request, body) = server.requests().remove(0);
+        assert_eq!(request, "POST /dns/retrieveByNameType/example.com/A/home");
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!((&body["apikey"], &body["secretapikey"]), (&json!("pk"), &json!("sk")));
+    }
+
+    #[tokio::test]
+    async fn edit_the_only_record_by_name_and_type() {
+        let server = server(
+            r#"{"status": "SUCCESS", "records": [
+                {"id": "1", "name": "home.example.com", "type": "A", "content": "192.0.2.1", "ttl": "600"},
+                {"id": "3", "name": "home.example.com", "type": "TXT", "content": "hello", "ttl": "600"}
+            ]}"#,
+        )
+        .await;
+        let provider = porkbun(&server).await;
+        let record = provider.get_dns_record(IpType::V4).await.unwrap().remove(0);
+        // the record of another type doesn't count
+        assert!(record.unique);
+        provider
+            .update_dns_record(&record, &ips(&["192.0.2.3"])[0])
+            .await
+            .unwrap();
+        let (request, body) = server.requests().remove(1);
+        assert_eq!(request, "POST /dns/editByNameType/example.com/A/home");
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!((&body["content"], &body["ttl"]), (&json!("192.0.2.3"), &json!("600")));
+    }
+
+    #[tokio::test]
+    async fn edit_one_of_several_records_by_id() {
+        let server = server(RECORDS).await;
+        let provider = porkbun(&server).await;
+        let record = provider.get_dns_record(IpType::V4).await.unwrap().remove(1);
+        provider
+            .update_dns_record(&record, &ips(&["192.0.2.3"])[0])
+            .await
+            .unwrap();
+        assert_eq!(server.requests()[1].0, "POST /dns/edit/example.com/2");
+    }
+
+    #[tokio::test]
+    async fn request_error() {
+        let server = MockServer::start(|_, _| (400, r#"{"status": "ERROR", "message": "bad key"}"#.to_owned())).await;
+        let provider = porkbun(&server).await;
+        let err = provider.get_dns_record(IpType::V4).await.unwrap_err();
+        assert_eq!(err.to_string(), "porkbun error ERROR: bad key");
+    }
+}
@@ -0,0 +1,223 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use log::debug;
+use reqwest::{Client, RequestBuilder};
+use serde::Deserialize;
+
+use super::{
+    create_in_record_set,
+    delete_from_record_set,
+    record_type_from_family,
+    update_in_record_set,
+    zone_of,
+    Provider,
+};
+use crate::IpType;
+
+const DEFAULT_SERVER_ID: &str = "localhost";
+
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub ip: IpAddr,
+    pub ttl: u32,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.ip, self.ttl)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorResponse {
+    error: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Record {
+    content: String,
+    #[serde(default)]
+    disabled: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct RecordSet {
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    ttl: u32,
+    #[serde(default)]
+    records: Vec<Record>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Zone {
+    #[serde(default)]
+    rrsets: Vec<RecordSet>,
+}
+
+pub struct PowerDns {
+    /// Fully qualified name with the trailing dot
+    name: String,
+    zone_url: String,
+    api_key: String,
+    notify: bool,
+    client: Client,
+}
+
+impl PowerDns {
+    pub async fn create<A: AsRef<str>, K: AsRef<str>, D: AsRef<str>>(
+        api_url: A,
+        api_key: K,
+        dns: D,
+        zone: Option<&str>,
+        server_id: Option<&str>,
+        notify: bool,
+    ) -> Result<Self> {
+        let dns = dns.as_ref().trim_end_matches('.').to_owned();
+
+        let zone = zone_of(&dns, zone)?;
+        let zone_url = format!(
+            "{}/servers/{}/zones/{}.",
+            api_url.as_ref().trim_end_matches('/'),
+            server_id.unwrap_or(DEFAULT_SERVER_ID),
+            zone
+        );
+
+        Ok(PowerDns {
+            name: format!("{}.", dns),
+            zone_url,
+            api_key: api_key.as_ref().to_owned(),
+            notify,
+            client: Client::new(),
+        })
+    }
+
+    async fn send(&self, builder: RequestBuilder) -> Result<String> {
+        let response = builder.header("X-API-Key", &self.api_key).send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            match serde_json::from_str::<ErrorResponse>(&text) {
+                Ok(err) => bail!("powerdns error {}: {}", status, err.error),
+                Err(_) => bail!("powerdns error {}: {}", status, text),
+            }
+        }
+        Ok(text)
+    }
+
+    /// The enabled records of the set and the addresses of the disabled ones
+    async fn get_record_set(&self, kind: &str) -> Result<(Vec<DNSRecord>, Vec<IpAddr>)> {
+        let builder = self
+            .client
+            .get(&self.zone_url)
+            .query(&[("rrset_name", self.name.as_str()), ("rrset_type", kind)]);
+        let zone = serde_json::from_str::<Zone>(&self.send(builder).await?)?;
+        let mut records = vec![];
+        let mut disabled = vec![];
+        // older servers ignore the filters and return the whole zone
+        for rrset in zone
+            .rrsets
+            .into_iter()
+            .filter(|v| v.name == self.name && v.kind == kind)
+        {
+            for record in rrset.records {
+                let ip = record.content.parse()?;
+                if record.disabled {
+                    disabled.push(ip);
+                } else {
+                    records.push(DNSRecord {
+                        ip,
+                        ttl: rrset.ttl,
+                    });
+                }
+            }
+        }
+        Ok((records, disabled))
+    }
+
+    /// Replace the enabled records of the set, the disabled ones are written back as they are,
+    /// the set is deleted once nothing is left
+    async fn patch_record_set(&self, kind: &str, ttl: u32, ips: &[IpAddr]) -> Result<()> {
+        let (_, disabled) = self.get_record_set(kind).await?;
+        let rrset = if ips.is_empty() && disabled.is_empty() {
+            json!({
+                "name": self.name,
+                "type": kind,
+                "changetype": "DELETE",
+            })
+        } else {
+            let records = ips
+                .iter()
+                .map(|v| json!({"content": v.to_string(), "disabled": false}))
+                .chain(
+                    disabled
+                        .iter()
+                        .filter(|v| !ips.contains(v))
+                        .map(|v| json!({"content": v.to_string(), "disabled": true})),
+                )
+                .collect::<Vec<_>>();
+            json!({
+                "name": self.name,
+                "type": kind,
+                "ttl": ttl,
+                "changetype": "REPLACE",
+                "records": records,
+            })
+        };
+        let json = json!({ "rrsets": [rrset] });
+        self.send(self.client.patch(&self.zone_url).json(&json)).await?;
+        if self.notify {
+            let url = format!("{}/notify", self.zone_url);
+            let text = self.send(self.client.put(url)).await?;
+            debug!("powerdns notify: {}", text);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Provider for PowerDns {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        Ok(self.get_record_set(record_type_from_family(family)).await?.0)
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        create_in_record_set(self, ip, ttl).await
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        update_in_record_set(self, &record.ip, ip, record.ttl).await
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        delete_from_record_set(self, &record.ip, record.ttl).await
+    }
+
+    fn supports_replace(&self) -> bool {
+        true
+    }
+
+    async fn replace_dns_records(
+        &self,
+        family: IpType,
+        records: &[Self::DNSRecord],
+        ips: &[IpAddr],
+        ttl: u32,
+    ) -> Result<()> {
+        let ttl = records.first().map(|v| v.ttl).unwrap_or(ttl);
+        self.patch_record_set(record_type_from_family(family), ttl, ips).await
+    }
+}
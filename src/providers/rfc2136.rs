@@ -0,0 +1,762 @@
+use std::fmt::{Display, Formatter};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use addr::parse_dns_name;
+use anyhow::{anyhow, bail, ensure, Result};
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use log::debug;
+use rand::random;
+use sha2::{Sha256, Sha512};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{lookup_host, TcpStream, UdpSocket};
+use tokio::time::{timeout, Duration};
+
+use super::Provider;
+use crate::IpType;
+
+const TYPE_A: u16 = 1;
+const TYPE_SOA: u16 = 6;
+const TYPE_AAAA: u16 = 28;
+const TYPE_TSIG: u16 = 250;
+const CLASS_IN: u16 = 1;
+const CLASS_NONE: u16 = 254;
+const CLASS_ANY: u16 = 255;
+const OPCODE_QUERY: u16 = 0;
+const OPCODE_UPDATE: u16 = 5;
+const FLAG_TC: u16 = 0x0200;
+const HEADER_LEN: usize = 12;
+const TSIG_FUDGE: u16 = 300;
+const EXCHANGE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub ip: IpAddr,
+    pub ttl: u32,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.ip, self.ttl)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TsigAlgorithm {
+    HmacSha256,
+    HmacSha512,
+}
+
+impl TsigAlgorithm {
+    fn name(&self) -> &'static str {
+        match self {
+            TsigAlgorithm::HmacSha256 => "hmac-sha256",
+            TsigAlgorithm::HmacSha512 => "hmac-sha512",
+        }
+    }
+
+    fn sign(&self, secret: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+        let mac = match self {
+            TsigAlgorithm::HmacSha256 => Hmac::<Sha256>::new_from_slice(secret)
+                .map_err(|_| anyhow!("invalid tsig secret"))?
+                .chain_update(data)
+                .finalize()
+                .into_bytes()
+                .to_vec(),
+            TsigAlgorithm::HmacSha512 => Hmac::<Sha512>::new_from_slice(secret)
+                .map_err(|_| anyhow!("invalid tsig secret"))?
+                .chain_update(data)
+                .finalize()
+                .into_bytes()
+                .to_vec(),
+        };
+        Ok(mac)
+    }
+}
+
+impl std::str::FromStr for TsigAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim_end_matches('.').to_ascii_lowercase().as_str() {
+            "hmac-sha256" => Ok(TsigAlgorithm::HmacSha256),
+            "hmac-sha512" => Ok(TsigAlgorithm::HmacSha512),
+            _ => bail!("unsupported tsig algorithm: {}", s),
+        }
+    }
+}
+
+struct TsigKey {
+    name: String,
+    algorithm: TsigAlgorithm,
+    secret: Vec<u8>,
+}
+
+pub struct Rfc2136 {
+    dns: String,
+    zone: String,
+    server: SocketAddr,
+    key: Option<TsigKey>,
+}
+
+impl Rfc2136 {
+    pub async fn create<S: AsRef<str>, D: AsRef<str>>(
+        server: S,
+        dns: D,
+        zone: Option<&str>,
+        tsig: Option<(&str, &str, TsigAlgorithm)>,
+    ) -> Result<Self> {
+        let server = server.as_ref();
+        let dns = dns.as_ref().trim_end_matches('.').to_owned();
+
+        let zone = match zone {
+            Some(zone) => zone.trim_end_matches('.').to_owned(),
+            None => {
+                let result = parse_dns_name(&dns).map_err(|err| anyhow!("can't parse dns {}: {}", dns, err))?;
+                result
+                    .root()
+                    .ok_or_else(|| anyhow!("can't find zone of {}", dns))?
+                    .to_owned()
+            },
+        };
+        debug!("zone name is {}", zone);
+
+        let server = resolve_server(server).await?;
+        debug!("primary nameserver is {}", server);
+
+        let key = match tsig {
+            Some((name, secret, algorithm)) => Some(TsigKey {
+                name: name.trim_end_matches('.').to_owned(),
+                algorithm,
+                secret: BASE64
+                    .decode(secret.trim())
+                    .map_err(|err| anyhow!("can't decode tsig secret: {}", err))?,
+            }),
+            None => None,
+        };
+
+        Ok(Rfc2136 {
+            dns,
+            zone,
+            server,
+            key,
+        })
+    }
+
+    async fn send_update(&self, updates: &[(u16, u32, IpAddr)]) -> Result<()> {
+        let id = random::<u16>();
+        let mut msg = header(id, OPCODE_UPDATE, 1, 0, updates.len() as u16);
+        encode_name(&mut msg, &self.zone)?;
+        msg.extend_from_slice(&TYPE_SOA.to_be_bytes());
+        msg.extend_from_slice(&CLASS_IN.to_be_bytes());
+        for (class, ttl, ip) in updates {
+            encode_name(&mut msg, &self.dns)?;
+            let (kind, rdata) = rdata_from_ip(ip);
+            msg.extend_from_slice(&kind.to_be_bytes());
+            msg.extend_from_slice(&class.to_be_bytes());
+            msg.extend_from_slice(&ttl.to_be_bytes());
+            msg.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            msg.extend_from_slice(&rdata);
+        }
+        let response = self.exchange(id, msg).await?;
+        ensure!(
+            response.rcode == 0,
+            "dns update for {} rejected: {}",
+            self.dns,
+            rcode_name(response.rcode)
+        );
+        Ok(())
+    }
+
+    async fn exchange(&self, id: u16, mut msg: Vec<u8>) -> Result<Response> {
+        let request_mac = match &self.key {
+            Some(key) => Some(sign_message(&mut msg, key, unix_time()?, None)?),
+            None => None,
+        };
+
+        let mut data = exchange_udp(self.server, &msg).await?;
+        ensure!(data.len() >= HEADER_LEN, "dns response too short");
+        if u16::from_be_bytes([data[2], data[3]]) & FLAG_TC != 0 {
+            debug!("dns response truncated, retry over tcp");
+            data = exchange_tcp(self.server, &msg).await?;
+        }
+
+        let response = Response::parse(&data)?;
+        ensure!(response.id == id, "dns response id mismatch");
+        if let (Some(key), Some(request_mac)) = (&self.key, request_mac) {
+            verify_message(&data, &response, key, &request_mac)?;
+        }
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl Provider for Rfc2136 {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        let kind = match family {
+            IpType::V4 => TYPE_A,
+            IpType::V6 => TYPE_AAAA,
+        };
+        let id = random::<u16>();
+        let mut msg = header(id, OPCODE_QUERY, 1, 0, 0);
+        encode_name(&mut msg, &self.dns)?;
+        msg.extend_from_slice(&kind.to_be_bytes());
+        msg.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+        let response = self.exchange(id, msg).await?;
+        match response.rcode {
+            0 => {},
+            // NXDOMAIN simply means no record yet
+            3 => return Ok(vec![]),
+            rcode => bail!("dns query for {} failed: {}", self.dns, rcode_name(rcode)),
+        }
+
+        let mut records = vec![];
+        for answer in response.answers {
+            if answer.kind != kind || answer.class != CLASS_IN || !answer.name.eq_ignore_ascii_case(&self.dns) {
+                continue;
+            }
+            let ip = match answer.rdata.len() {
+                4 => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(&answer.rdata[..])?)),
+                16 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(&answer.rdata[..])?)),
+                _ => bail!("malformed rdata of {}", self.dns),
+            };
+            records.push(DNSRecord {
+                ip,
+                ttl: answer.ttl,
+            });
+        }
+        Ok(records)
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        self.send_update(&[(CLASS_IN, ttl, *ip)]).await
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        self.send_update(&[(CLASS_NONE, 0, record.ip), (CLASS_IN, record.ttl, *ip)])
+            .await
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        self.send_update(&[(CLASS_NONE, 0, record.ip)]).await
+    }
+}
+
+async fn resolve_server(server: &str) -> Result<SocketAddr> {
+    if let Ok(addr) = server.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    if let Ok(ip) = server.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, 53));
+    }
+    let server = if server.contains(':') {
+        server.to_owned()
+    } else {
+        format!("{}:53", server)
+    };
+    let addr = lookup_host(&server).await?.next();
+    addr.ok_or_else(|| anyhow!("can't resolve nameserver {}", server))
+}
+
+async fn exchange_udp(server: SocketAddr, msg: &[u8]) -> Result<Vec<u8>> {
+    let local: SocketAddr = match server {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(server).await?;
+    socket.send(msg).await?;
+    let mut buf = vec![0; 65535];
+    let len = timeout(EXCHANGE_TIMEOUT, socket.recv(&mut buf))
+        .await
+        .map_err(|_| anyhow!("dns request to {} timed out", server))??;
+    buf.truncate(len);
+    Ok(buf)
+}
+
+async fn exchange_tcp(server: SocketAddr, msg: &[u8]) -> Result<Vec<u8>> {
+    let exchange = async {
+        let mut stream = TcpStream::connect(server).await?;
+        stream.write_all(&(msg.len() as u16).to_be_bytes()).await?;
+        stream.write_all(msg).await?;
+        let len = stream.read_u16().await?;
+        let mut buf = vec![0; len as usize];
+        stream.read_exact(&mut buf).await?;
+        Result::<_>::Ok(buf)
+    };
+    timeout(EXCHANGE_TIMEOUT, exchange)
+        .await
+        .map_err(|_| anyhow!("dns request to {} timed out", server))?
+}
+
+fn header(id: u16, opcode: u16, qdcount: u16, ancount: u16, nscount: u16) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(512);
+    msg.extend_from_slice(&id.to_be_bytes());
+    msg.extend_from_slice(&(opcode << 11).to_be_bytes());
+    msg.extend_from_slice(&qdcount.to_be_bytes());
+    msg.extend_from_slice(&ancount.to_be_bytes());
+    msg.extend_from_slice(&nscount.to_be_bytes());
+    msg.extend_from_slice(&0u16.to_be_bytes());
+    msg
+}
+
+fn rdata_from_ip(ip: &IpAddr) -> (u16, Vec<u8>) {
+    match ip {
+        IpAddr::V4(ip) => (TYPE_A, ip.octets().to_vec()),
+        IpAddr::V6(ip) => (TYPE_AAAA, ip.octets().to_vec()),
+    }
+}
+
+fn encode_name(buf: &mut Vec<u8>, name: &str) -> Result<()> {
+    for label in name.trim_end_matches('.').split('.').filter(|v| !v.is_empty()) {
+        ensure!(label.len() < 64, "label too long in {}", name);
+        buf.push(label.len() as u8);
+        buf.extend_from_slice(label.as_bytes());
+    }
+    buf.push(0);
+    Ok(())
+}
+
+/// Names inside the TSIG digest are always in canonical (lowercase, uncompressed) form
+fn encode_canonical_name(buf: &mut Vec<u8>, name: &str) -> Result<()> {
+    encode_name(buf, &name.to_ascii_lowercase())
+}
+
+fn read_name(msg: &[u8], mut pos: usize) -> Result<(String, usize)> {
+    let mut labels = vec![];
+    let mut end = None;
+    let mut jumps = 0;
+    loop {
+        let len = *msg.get(pos).ok_or_else(|| anyhow!("malformed dns name"))? as usize;
+        match len & 0xC0 {
+            0x00 if len == 0 => {
+                pos += 1;
+                break;
+            },
+            0x00 => {
+                let label = msg
+                    .get(pos + 1..pos + 1 + len)
+                    .ok_or_else(|| anyhow!("malformed dns name"))?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                pos += 1 + len;
+            },
+            0xC0 => {
+                let low = *msg.get(pos + 1).ok_or_else(|| anyhow!("malformed dns name"))? as usize;
+                jumps += 1;
+                ensure!(jumps < 64, "dns name compression loop");
+                end.get_or_insert(pos + 2);
+                pos = ((len & 0x3F) << 8) | low;
+            },
+            _ => bail!("unsupported dns label type"),
+        }
+    }
+    Ok((labels.join("."), end.unwrap_or(pos)))
+}
+
+fn read_u16(msg: &[u8], pos: usize) -> Result<u16> {
+    let bytes = msg.get(pos..pos + 2).ok_or_else(|| anyhow!("dns message too short"))?;
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(msg: &[u8], pos: usize) -> Result<u32> {
+    Ok(((read_u16(msg, pos)? as u32) << 16) | read_u16(msg, pos + 2)? as u32)
+}
+
+struct ResourceRecord {
+    name: String,
+    kind: u16,
+    class: u16,
+    ttl: u32,
+    rdata_offset: usize,
+    rdata: Vec<u8>,
+}
+
+fn read_record(msg: &[u8], pos: usize) -> Result<(ResourceRecord, usize)> {
+    let (name, pos) = read_name(msg, pos)?;
+    let kind = read_u16(msg, pos)?;
+    let class = read_u16(msg, pos + 2)?;
+    let ttl = read_u32(msg, pos + 4)?;
+    let len = read_u16(msg, pos + 8)? as usize;
+    let rdata_offset = pos + 10;
+    let rdata = msg
+        .get(rdata_offset..rdata_offset + len)
+        .ok_or_else(|| anyhow!("dns message too short"))?
+        .to_vec();
+    let record = ResourceRecord {
+        name,
+        kind,
+        class,
+        ttl,
+        rdata_offset,
+        rdata,
+    };
+    Ok((record, rdata_offset + len))
+}
+
+struct Response {
+    id: u16,
+    rcode: u16,
+    answers: Vec<ResourceRecord>,
+    /// The trailing TSIG record and the offset it starts at
+    tsig: Option<(usize, ResourceRecord)>,
+}
+
+impl Response {
+    fn parse(msg: &[u8]) -> Result<Self> {
+        let id = read_u16(msg, 0)?;
+        let rcode = read_u16(msg, 2)? & 0x000F;
+        let counts = [
+            read_u16(msg, 4)?,
+            read_u16(msg, 6)?,
+            read_u16(msg, 8)?,
+            read_u16(msg, 10)?,
+        ];
+        let mut pos = HEADER_LEN;
+        for _ in 0..counts[0] {
+            pos = read_name(msg, pos)?.1 + 4;
+        }
+        let mut answers = vec![];
+        let mut tsig = None;
+        for (section, count) in counts.iter().enumerate().skip(1) {
+            for _ in 0..*count {
+                let start = pos;
+                let (record, next) = read_record(msg, pos)?;
+                pos = next;
+                if section == 1 {
+                    answers.push(record);
+                } else if section == 3 && record.kind == TYPE_TSIG {
+                    tsig = Some((start, record));
+                }
+            }
+        }
+        Ok(Response {
+            id,
+            rcode,
+            answers,
+            tsig,
+        })
+    }
+}
+
+fn unix_time() -> Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+fn push_u48(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_be_bytes()[2..]);
+}
+
+fn tsig_variables(
+    buf: &mut Vec<u8>,
+    key_name: &str,
+    algorithm: &str,
+    time_signed: u64,
+    fudge: u16,
+    error: u16,
+    other: &[u8],
+) -> Result<()> {
+    encode_canonical_name(buf, key_name)?;
+    buf.extend_from_slice(&CLASS_ANY.to_be_bytes());
+    buf.extend_from_slice(&0u32.to_be_bytes());
+    encode_canonical_name(buf, algorithm)?;
+    push_u48(buf, time_signed);
+    buf.extend_from_slice(&fudge.to_be_bytes());
+    buf.extend_from_slice(&error.to_be_bytes());
+    buf.extend_from_slice(&(other.len() as u16).to_be_bytes());
+    buf.extend_from_slice(other);
+    Ok(())
+}
+
+/// Append a TSIG record (RFC 8945) to `msg` and return the MAC for verifying the response,
+/// a response is signed along with the MAC of its request
+fn sign_message(msg: &mut Vec<u8>, key: &TsigKey, time_signed: u64, request_mac: Option<&[u8]>) -> Result<Vec<u8>> {
+    let mut data = vec![];
+    if let Some(request_mac) = request_mac {
+        data.extend_from_slice(&(request_mac.len() as u16).to_be_bytes());
+        data.extend_from_slice(request_mac);
+    }
+    data.extend_from_slice(msg);
+    tsig_variables(
+        &mut data,
+        &key.name,
+        key.algorithm.name(),
+        time_signed,
+        TSIG_FUDGE,
+        0,
+        &[],
+    )?;
+    let mac = key.algorithm.sign(&key.secret, &data)?;
+
+    let mut rdata = vec![];
+    encode_name(&mut rdata, key.algorithm.name())?;
+    push_u48(&mut rdata, time_signed);
+    rdata.extend_from_slice(&TSIG_FUDGE.to_be_bytes());
+    rdata.extend_from_slice(&(mac.len() as u16).to_be_bytes());
+    rdata.extend_from_slice(&mac);
+    rdata.extend_from_slice(&msg[0..2]);
+    rdata.extend_from_slice(&0u16.to_be_bytes());
+    rdata.extend_from_slice(&0u16.to_be_bytes());
+
+    encode_name(msg, &key.name)?;
+    msg.extend_from_slice(&TYPE_TSIG.to_be_bytes());
+    msg.extend_from_slice(&CLASS_ANY.to_be_bytes());
+    msg.extend_from_slice(&0u32.to_be_bytes());
+    msg.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+    msg.extend_from_slice(&rdata);
+    let arcount = read_u16(msg, 10)? + 1;
+    msg[10..12].copy_from_slice(&arcount.to_be_bytes());
+    Ok(mac)
+}
+
+fn verify_message(msg: &[u8], response: &Response, key: &TsigKey, request_mac: &[u8]) -> Result<()> {
+    let (start, tsig) = match &response.tsig {
+        Some(v) => v,
+        None => bail!("dns response is not signed: {}", rcode_name(response.rcode)),
+    };
+    ensure!(
+        tsig.name.eq_ignore_ascii_case(&key.name),
+        "dns response signed by unknown key"
+    );
+
+    let (algorithm, pos) = read_name(msg, tsig.rdata_offset)?;
+    let time_signed = ((read_u16(msg, pos)? as u64) << 32) | read_u32(msg, pos + 2)? as u64;
+    let fudge = read_u16(msg, pos + 6)?;
+    let mac_len = read_u16(msg, pos + 8)? as usize;
+    let mac = msg
+        .get(pos + 10..pos + 10 + mac_len)
+        .ok_or_else(|| anyhow!("dns message too short"))?;
+    let pos = pos + 10 + mac_len;
+    let original_id = read_u16(msg, pos)?;
+    let error = read_u16(msg, pos + 2)?;
+    let other_len = read_u16(msg, pos + 4)? as usize;
+    let other = msg
+        .get(pos + 6..pos + 6 + other_len)
+        .ok_or_else(|| anyhow!("dns message too short"))?;
+
+    match error {
+        0 => {},
+        16 => bail!("tsig verification failed on server: BADSIG"),
+        17 => bail!("tsig verification failed on server: BADKEY"),
+        18 => bail!("tsig verification failed on server: BADTIME"),
+        error => bail!("tsig verification failed on server: error {}", error),
+    }
+    ensure!(
+        algorithm.eq_ignore_ascii_case(key.algorithm.name()),
+        "dns response signed with unexpected algorithm {}",
+        algorithm
+    );
+
+    let mut data = Vec::with_capacity(msg.len());
+    data.extend_from_slice(&(request_mac.len() as u16).to_be_bytes());
+    data.extend_from_slice(request_mac);
+    let mut unsigned = msg[..*start].to_vec();
+    unsigned[0..2].copy_from_slice(&original_id.to_be_bytes());
+    let arcount = read_u16(&unsigned, 10)? - 1;
+    unsigned[10..12].copy_from_slice(&arcount.to_be_bytes());
+    data.extend_from_slice(&unsigned);
+    tsig_variables(&mut data, &tsig.name, &algorithm, time_signed, fudge, error, other)?;
+    let expected = key.algorithm.sign(&key.secret, &data)?;
+    ensure!(expected == mac, "dns response has a bad tsig signature");
+
+    let now = unix_time()?;
+    ensure!(
+        now.abs_diff(time_signed) <= fudge as u64,
+        "dns response tsig time is out of range"
+    );
+    Ok(())
+}
+
+fn rcode_name(rcode: u16) -> &'static str {
+    match rcode {
+        0 => "NOERROR",
+        1 => "FORMERR",
+        2 => "SERVFAIL",
+        3 => "NXDOMAIN",
+        4 => "NOTIMP",
+        5 => "REFUSED",
+        6 => "YXDOMAIN",
+        7 => "YXRRSET",
+        8 => "NXRRSET",
+        9 => "NOTAUTH",
+        10 => "NOTZONE",
+        _ => "UNKNOWN",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_key(algorithm: TsigAlgorithm) -> TsigKey {
+        TsigKey {
+            name: "tsig-key".to_owned(),
+            algorithm,
+            secret: b"0123456789abcdef".to_vec(),
+        }
+    }
+
+    fn update_message() -> Vec<u8> {
+        let mut msg = header(0x1234, OPCODE_UPDATE, 1, 0, 0);
+        encode_name(&mut msg, "example.com").unwrap();
+        msg.extend_from_slice(&TYPE_SOA.to_be_bytes());
+        msg.extend_from_slice(&CLASS_IN.to_be_bytes());
+        msg
+    }
+
+    /// A response to a query of `home.example.com A`, the answer name is compressed
+    fn query_response() -> Vec<u8> {
+        let mut msg = header(0x1234, OPCODE_QUERY, 1, 1, 0);
+        msg[2] |= 0x80;
+        encode_name(&mut msg, "home.example.com").unwrap();
+        msg.extend_from_slice(&TYPE_A.to_be_bytes());
+        msg.extend_from_slice(&CLASS_IN.to_be_bytes());
+        msg.extend_from_slice(&[0xC0, HEADER_LEN as u8]);
+        msg.extend_from_slice(&TYPE_A.to_be_bytes());
+        msg.extend_from_slice(&CLASS_IN.to_be_bytes());
+        msg.extend_from_slice(&600u32.to_be_bytes());
+        msg.extend_from_slice(&4u16.to_be_bytes());
+        msg.extend_from_slice(&[203, 0, 113, 8]);
+        msg
+    }
+
+    #[test]
+    fn name_round_trip() {
+        let mut buf = vec![];
+        encode_name(&mut buf, "home.example.com.").unwrap();
+        assert_eq!(buf, b"\x04home\x07example\x03com\x00");
+        assert_eq!(read_name(&buf, 0).unwrap(), ("home.example.com".to_owned(), buf.len()));
+
+        let mut buf = vec![];
+        encode_name(&mut buf, "").unwrap();
+        assert_eq!(read_name(&buf, 0).unwrap(), (String::new(), 1));
+    }
+
+    #[test]
+    fn name_too_long_label() {
+        let mut buf = vec![];
+        assert!(encode_name(&mut buf, &format!("{}.com", "a".repeat(64))).is_err());
+    }
+
+    #[test]
+    fn name_decompression() {
+        let mut msg = vec![0; 4];
+        encode_name(&mut msg, "example.com").unwrap();
+        let start = msg.len();
+        msg.extend_from_slice(b"\x04home\xC0\x04");
+        assert_eq!(
+            read_name(&msg, start).unwrap(),
+            ("home.example.com".to_owned(), msg.len())
+        );
+    }
+
+    #[test]
+    fn name_compression_loop() {
+        // a pointer to itself, and two pointers to each other
+        assert!(read_name(&[0xC0, 0x00], 0).is_err());
+        assert!(read_name(&[0xC0, 0x02, 0xC0, 0x00], 0).is_err());
+        // a pointer out of the message
+        assert!(read_name(&[0xC0, 0x10], 0).is_err());
+        // a label longer than the message
+        assert!(read_name(&[0x05, b'a', b'b'], 0).is_err());
+    }
+
+    #[test]
+    fn hmac_vectors() {
+        // RFC 4231 test case 2
+        let data = b"what do ya want for nothing?";
+        let mac = TsigAlgorithm::HmacSha256.sign(b"Jefe", data).unwrap();
+        assert_eq!(
+            hex::encode(mac),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        let mac = TsigAlgorithm::HmacSha512.sign(b"Jefe", data).unwrap();
+        assert_eq!(
+            hex::encode(mac),
+            "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea250554\
+             9758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737"
+        );
+    }
+
+    #[test]
+    fn tsig_request_mac() {
+        // the MAC over the message and the TSIG variables of RFC 8945 section 4.3.3, computed independently
+        let key = test_key(TsigAlgorithm::HmacSha256);
+        let mut msg = update_message();
+        let unsigned_len = msg.len();
+        let mac = sign_message(&mut msg, &key, 1_700_000_000, None).unwrap();
+        assert_eq!(
+            hex::encode(&mac),
+            "86b67f85a2e00ca0d2efbfa698f709e64897335a740aae1a8526e2d10b12830f"
+        );
+
+        let response = Response::parse(&msg).unwrap();
+        assert_eq!(read_u16(&msg, 10).unwrap(), 1);
+        let (start, tsig) = response.tsig.unwrap();
+        assert_eq!(start, unsigned_len);
+        assert_eq!(tsig.name, "tsig-key");
+        assert_eq!(tsig.class, CLASS_ANY);
+        assert!(tsig.rdata.windows(mac.len()).any(|v| v == &mac[..]));
+    }
+
+    #[test]
+    fn tsig_verify_response() {
+        let key = test_key(TsigAlgorithm::HmacSha512);
+        let mut request = update_message();
+        let request_mac = sign_message(&mut request, &key, unix_time().unwrap(), None).unwrap();
+
+        let mut msg = query_response();
+        sign_message(&mut msg, &key, unix_time().unwrap(), Some(&request_mac)).unwrap();
+        let response = Response::parse(&msg).unwrap();
+        verify_message(&msg, &response, &key, &request_mac).unwrap();
+
+        // signed for another request
+        assert!(verify_message(&msg, &response, &key, &[0; 64]).is_err());
+        // tampered answer
+        let mut tampered = msg.clone();
+        let pos = tampered.iter().position(|v| *v == 203).unwrap();
+        tampered[pos] = 198;
+        let response = Response::parse(&tampered).unwrap();
+        assert!(verify_message(&tampered, &response, &key, &request_mac).is_err());
+        // too old
+        let mut old = query_response();
+        sign_message(&mut old, &key, unix_time().unwrap() - 3600, Some(&request_mac)).unwrap();
+        let response = Response::parse(&old).unwrap();
+        assert!(verify_message(&old, &response, &key, &request_mac).is_err());
+    }
+
+    #[test]
+    fn parse_response() {
+        let msg = query_response();
+        let response = Response::parse(&msg).unwrap();
+        assert_eq!(response.id, 0x1234);
+        assert_eq!(response.rcode, 0);
+        assert_eq!(response.answers.len(), 1);
+        let answer = &response.answers[0];
+        assert_eq!(answer.name, "home.example.com");
+        assert_eq!(answer.kind, TYPE_A);
+        assert_eq!(answer.ttl, 600);
+        assert_eq!(answer.rdata, vec![203, 0, 113, 8]);
+        assert!(response.tsig.is_none());
+    }
+
+    #[test]
+    fn parse_truncated_response() {
+        let msg = query_response();
+        for len in 0..msg.len() {
+            assert!(Response::parse(&msg[..len]).is_err(), "parsed {} bytes", len);
+        }
+    }
+}
@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+use std::env;
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use log::debug;
+use reqwest::{Client, Method, Url};
+use serde::Deserialize;
+
+use super::{
+    create_in_record_set,
+    delete_from_record_set,
+    hmac_sha256,
+    percent_encode,
+    record_type_from_family,
+    sha256_hex,
+    update_in_record_set,
+    Provider,
+};
+use crate::IpType;
+
+const DEFAULT_ENDPOINT: &str = "https://route53.amazonaws.com";
+const DEFAULT_REGION: &str = "us-east-1";
+const API_VERSION: &str = "2013-04-01";
+
+/// One value of the record set, route53 has no record id so the
+/// (name, type) pair is shared by every record of the same family
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub ip: IpAddr,
+    pub ttl: u32,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.ip, self.ttl)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+pub struct Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+impl Credentials {
+    /// Find credentials in the order of: config, environment variables, shared credentials file
+    pub fn load(
+        access_key_id: Option<&str>,
+        secret_access_key: Option<&str>,
+        session_token: Option<&str>,
+        profile: Option<&str>,
+    ) -> Result<Self> {
+        if let (Some(access_key_id), Some(secret_access_key)) = (access_key_id, secret_access_key) {
+            return Ok(Credentials {
+                access_key_id: access_key_id.to_owned(),
+                secret_access_key: secret_access_key.to_owned(),
+                session_token: session_token.map(|v| v.to_owned()),
+            });
+        }
+
+        if let (Ok(access_key_id), Ok(secret_access_key)) =
+            (env::var("AWS_ACCESS_KEY_ID"), env::var("AWS_SECRET_ACCESS_KEY"))
+        {
+            debug!("using aws credentials from environment variables");
+            return Ok(Credentials {
+                access_key_id,
+                secret_access_key,
+                session_token: env::var("AWS_SESSION_TOKEN").ok(),
+            });
+        }
+
+        let path = match env::var_os("AWS_SHARED_CREDENTIALS_FILE") {
+            Some(path) => PathBuf::from(path),
+            None => {
+                let home = env::var_os("HOME")
+                    .or_else(|| env::var_os("USERPROFILE"))
+                    .ok_or_else(|| anyhow!("can't find aws credentials"))?;
+                PathBuf::from(home).join(".aws").join("credentials")
+            },
+        };
+        let profile = match profile {
+            Some(profile) => profile.to_owned(),
+            None => env::var("AWS_PROFILE").unwrap_or_else(|_| "default".to_owned()),
+        };
+        debug!("using aws credentials from {:?} with profile {}", path, profile);
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|err| anyhow!("can't read aws credentials file {:?}: {}", path, err))?;
+        let mut section = String::new();
+        let mut values = HashMap::new();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                section = line[1..line.len() - 1].trim().to_owned();
+                continue;
+            }
+            if section != profile {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=') {
+                values.insert(key.trim().to_owned(), value.trim().to_owned());
+            }
+        }
+        Ok(Credentials {
+            access_key_id: values
+                .remove("aws_access_key_id")
+                .ok_or_else(|| anyhow!("missing aws_access_key_id in profile {}", profile))?,
+            secret_access_key: values
+                .remove("aws_secret_access_key")
+                .ok_or_else(|| anyhow!("missing aws_secret_access_key in profile {}", profile))?,
+            session_token: values.remove("aws_session_token"),
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ListResourceRecordSetsResponse {
+    #[serde(rename = "ResourceRecordSets")]
+    record_sets: ResourceRecordSets,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct ResourceRecordSets {
+    #[serde(rename = "ResourceRecordSet", default)]
+    items: Vec<ResourceRecordSet>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ResourceRecordSet {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "Type")]
+    kind: String,
+    #[serde(rename = "TTL")]
+    ttl: Option<u32>,
+    #[serde(rename = "ResourceRecords", default)]
+    records: ResourceRecords,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct ResourceRecords {
+    #[serde(rename = "ResourceRecord", default)]
+    items: Vec<ResourceRecord>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ResourceRecord {
+    #[serde(rename = "Value")]
+    value: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorResponse {
+    #[serde(rename = "Error")]
+    error: ErrorDetail,
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorDetail {
+    #[serde(rename = "Code")]
+    code: String,
+    #[serde(rename = "Message")]
+    message: String,
+}
+
+pub struct Route53 {
+    dns: String,
+    hosted_zone_id: String,
+    endpoint: Url,
+    region: String,
+    client: Client,
+    cred: Credentials,
+}
+
+impl Route53 {
+    pub async fn create<H: AsRef<str>, D: AsRef<str>>(
+        hosted_zone_id: H,
+        dns: D,
+        endpoint: Option<&str>,
+        region: Option<&str>,
+        cred: Credentials,
+    ) -> Result<Self> {
+        let hosted_zone_id = hosted_zone_id.as_ref().trim_start_matches("/hostedzone/").to_owned();
+        let mut dns = dns.as_ref().to_owned();
+        if !dns.ends_with('.') {
+            dns.push('.');
+        }
+        let endpoint = Url::parse(endpoint.unwrap_or(DEFAULT_ENDPOINT))?;
+        let region = region.unwrap_or(DEFAULT_REGION).to_owned();
+
+        Ok(Route53 {
+            dns,
+            hosted_zone_id,
+            endpoint,
+            region,
+            client: Client::new(),
+            cred,
+        })
+    }
+
+    async fn request(&self, method: Method, query: &[(&str, &str)], body: String) -> Result<String> {
+        let path = format!("/{}/hostedzone/{}/rrset", API_VERSION, self.hosted_zone_id);
+        let mut url = self.endpoint.join(&path)?;
+        let mut query = query.to_vec();
+        query.sort();
+        let canonical_query = query
+            .iter()
+            .map(|(k, v)| format!("{}={}", percent_encode(k), percent_encode(v)))
+            .collect::<Vec<_>>()
+            .join("&");
+        if !canonical_query.is_empty() {
+            url.set_query(Some(&canonical_query));
+        }
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_owned(),
+        };
+
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let mut headers = vec![("host", host), ("x-amz-date", amz_date.clone())];
+        if let Some(token) = &self.cred.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let signed_headers = headers.iter().map(|(k, _)| *k).collect::<Vec<_>>().join(";");
+        let canonical_headers = headers
+            .iter()
+            .map(|(k, v)| format!("{}:{}\n", k, v.trim()))
+            .collect::<String>();
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            method.as_str(),
+            url.path(),
+            canonical_query,
+            canonical_headers,
+            signed_headers,
+            sha256_hex(body.as_bytes())
+        );
+        let scope = format!("{}/{}/route53/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            sha256_hex(canonical_request.as_bytes())
+        );
+        let key = hmac_sha256(
+            format!("AWS4{}", self.cred.secret_access_key).as_bytes(),
+            date.as_bytes(),
+        );
+        let key = hmac_sha256(&key, self.region.as_bytes());
+        let key = hmac_sha256(&key, b"route53");
+        let key = hmac_sha256(&key, b"aws4_request");
+        let signature = hex::encode(hmac_sha256(&key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.cred.access_key_id, scope, signed_headers, signature
+        );
+
+        let mut builder = self
+            .client
+            .request(method, url)
+            .header(reqwest::header::AUTHORIZATION, authorization);
+        for (k, v) in headers.into_iter().skip(1) {
+            builder = builder.header(k, v);
+        }
+        if !body.is_empty() {
+            builder = builder
+                .header(reqwest::header::CONTENT_TYPE, "application/xml")
+                .body(body);
+        }
+        let response = builder.send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            match quick_xml::de::from_str::<ErrorResponse>(&text) {
+                Ok(err) => bail!("route53 error {}: {}", err.error.code, err.error.message),
+                Err(_) => bail!("route53 error {}: {}", status, text),
+            }
+        }
+        Ok(text)
+    }
+
+    async fn get_record_set(&self, kind: &str) -> Result<Vec<DNSRecord>> {
+        let text = self
+            .request(
+                Method::GET,
+                &[("name", &self.dns), ("type", kind), ("maxitems", "1")],
+                String::new(),
+            )
+            .await?;
+        let response = quick_xml::de::from_str::<ListResourceRecordSetsResponse>(&text)?;
+        let mut records = vec![];
+        for set in response.record_sets.items {
+            if !set.name.eq_ignore_ascii_case(&self.dns) || set.kind != kind {
+                continue;
+            }
+            for record in set.records.items {
+                records.push(DNSRecord {
+                    ip: record.value.parse()?,
+                    ttl: set.ttl.unwrap_or_default(),
+                });
+            }
+        }
+        Ok(records)
+    }
+
+    async fn change_record_set(&self, action: &str, kind: &str, ttl: u32, ips: &[IpAddr]) -> Result<()> {
+        let values = ips
+            .iter()
+            .map(|ip| format!("<ResourceRecord><Value>{}</Value></ResourceRecord>", ip))
+            .collect::<String>();
+        let body = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?><ChangeResourceRecordSetsRequest xmlns="https://route53.amazonaws.com/doc/{}/"><ChangeBatch><Changes><Change><Action>{}</Action><ResourceRecordSet><Name>{}</Name><Type>{}</Type><TTL>{}</TTL><ResourceRecords>{}</ResourceRecords></ResourceRecordSet></Change></Changes></ChangeBatch></ChangeResourceRecordSetsRequest>"#,
+            API_VERSION,
+            action,
+            quick_xml::escape::escape(&self.dns),
+            kind,
+            ttl,
+            values
+        );
+        self.request(Method::POST, &[], body).await?;
+        Ok(())
+    }
+
+    /// Route53 only knows about whole record sets, so write back every value of the set
+    async fn write_record_set(&self, kind: &str, ttl: u32, olds: &[DNSRecord], ips: &[IpAddr]) -> Result<()> {
+        if ips.is_empty() {
+            if olds.is_empty() {
+                return Ok(());
+            }
+            // DELETE must match the current record set exactly
+            let ttl = olds[0].ttl;
+            let olds = olds.iter().map(|v| v.ip).collect::<Vec<_>>();
+            self.change_record_set("DELETE", kind, ttl, &olds).await
+        } else {
+            self.change_record_set("UPSERT", kind, ttl, ips).await
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for Route53 {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        self.get_record_set(record_type_from_family(family)).await
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        create_in_record_set(self, ip, ttl).await
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        update_in_record_set(self, &record.ip, ip, record.ttl).await
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        delete_from_record_set(self, &record.ip, record.ttl).await
+    }
+
+    fn supports_replace(&self) -> bool {
+        true
+    }
+
+    async fn replace_dns_records(
+        &self,
+        family: IpType,
+        records: &[Self::DNSRecord],
+        ips: &[IpAddr],
+        ttl: u32,
+    ) -> Result<()> {
+        let ttl = records.first().map(|v| v.ttl).unwrap_or(ttl);
+        self.write_record_set(record_type_from_family(family), ttl, records, ips)
+            .await
+    }
+}
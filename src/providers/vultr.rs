@@ -0,0 +1,213 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{find_domain_id, record_type_from_ip, relative_name, Provider};
+use crate::IpType;
+
+const DEFAULT_API_URL: &str = "https://api.vultr.com/v2";
+const PER_PAGE: u32 = 500;
+
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub id: String,
+    pub ip: IpAddr,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorResponse {
+    error: String,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct Meta {
+    #[serde(default)]
+    links: Links,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct Links {
+    #[serde(default)]
+    next: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct DomainsResponse {
+    #[serde(default)]
+    domains: Vec<Domain>,
+    #[serde(default)]
+    meta: Meta,
+}
+
+#[derive(Deserialize, Debug)]
+struct Domain {
+    domain: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct RecordsResponse {
+    #[serde(default)]
+    records: Vec<Record>,
+    #[serde(default)]
+    meta: Meta,
+}
+
+#[derive(Deserialize, Debug)]
+struct Record {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    name: String,
+    data: String,
+}
+
+pub struct Vultr {
+    name: String,
+    api_url: String,
+    token: String,
+    client: Client,
+    /// Vultr identifies a domain by its name
+    domain_id: String,
+}
+
+impl Vultr {
+    pub async fn create<T: AsRef<str>, D: AsRef<str>>(token: T, dns: D, api_url: Option<&str>) -> Result<Self> {
+        let dns = dns.as_ref();
+        let mut provider = Vultr {
+            name: String::new(),
+            api_url: api_url.unwrap_or(DEFAULT_API_URL).trim_end_matches('/').to_owned(),
+            token: token.as_ref().to_owned(),
+            client: Client::new(),
+            domain_id: String::new(),
+        };
+
+        let url = format!("{}/domains", provider.api_url);
+        let mut domains = vec![];
+        let mut cursor = String::new();
+        loop {
+            let response: DomainsResponse = provider
+                .send(
+                    provider
+                        .client
+                        .get(&url)
+                        .query(&[("per_page", PER_PAGE.to_string()), ("cursor", cursor)]),
+                )
+                .await?;
+            domains.extend(response.domains.into_iter().map(|v| (v.domain.clone(), v.domain)));
+            if response.meta.links.next.is_empty() {
+                break;
+            }
+            cursor = response.meta.links.next;
+        }
+        let (domain_id, domain) = find_domain_id(dns, &domains)?;
+
+        provider.name = relative_name(dns, &domain)?;
+        provider.domain_id = domain_id;
+        Ok(provider)
+    }
+
+    async fn send<T: DeserializeOwned>(&self, builder: RequestBuilder) -> Result<T> {
+        let response = builder.bearer_auth(&self.token).send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            match serde_json::from_str::<ErrorResponse>(&text) {
+                Ok(err) => bail!("vultr error {}: {}", status, err.error),
+                Err(_) => bail!("vultr error {}: {}", status, text),
+            }
+        }
+        if text.is_empty() {
+            return Ok(serde_json::from_value(Value::Null)?);
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+#[async_trait]
+impl Provider for Vultr {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        let url = format!("{}/domains/{}/records", self.api_url, self.domain_id);
+        let mut result = vec![];
+        let mut cursor = String::new();
+        loop {
+            let response: RecordsResponse = self
+                .send(
+                    self.client
+                        .get(&url)
+                        .query(&[("per_page", PER_PAGE.to_string()), ("cursor", cursor)]),
+                )
+                .await?;
+
+            for record in &response.records {
+                if record.name != self.name {
+                    continue;
+                }
+                match (family, &*record.kind) {
+                    (IpType::V6, "AAAA") | (IpType::V4, "A") => {
+                        result.push(DNSRecord {
+                            id: record.id.clone(),
+                            ip: record.data.parse()?,
+                        });
+                    },
+                    _ => {},
+                }
+            }
+
+            if response.meta.links.next.is_empty() {
+                break;
+            }
+            cursor = response.meta.links.next;
+        }
+        Ok(result)
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        let url = format!("{}/domains/{}/records", self.api_url, self.domain_id);
+        let _: Value = self
+            .send(self.client.post(url).json(&json!({
+                "type": record_type_from_ip(ip),
+                "name": self.name,
+                "data": ip.to_string(),
+                "ttl": ttl,
+            })))
+            .await?;
+        Ok(())
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        let url = format!("{}/domains/{}/records/{}", self.api_url, self.domain_id, record.id);
+        let _: Value = self
+            .send(self.client.patch(url).json(&json!({
+                "data": ip.to_string(),
+            })))
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        let url = format!("{}/domains/{}/records/{}", self.api_url, self.domain_id, record.id);
+        let _: Value = self.send(self.client.delete(url)).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,635 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use log::{debug, info};
+
+use super::{
+    create_in_record_set,
+    delete_from_record_set,
+    lock_local_files,
+    record_type_from_family,
+    run_command,
+    update_in_record_set,
+    write_file_atomic,
+    zone_of,
+    Provider,
+};
+use crate::IpType;
+
+#[derive(PartialOrd, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct DNSRecord {
+    pub ip: IpAddr,
+    pub ttl: u32,
+}
+
+impl Display for DNSRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.ip, self.ttl)
+    }
+}
+
+impl AsRef<IpAddr> for DNSRecord {
+    #[inline]
+    fn as_ref(&self) -> &IpAddr {
+        &self.ip
+    }
+}
+
+/// How the SOA serial is bumped after a change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerialMode {
+    /// `YYYYMMDDnn`, falling back to an increment when the serial is already ahead of today
+    Date,
+    Increment,
+}
+
+impl SerialMode {
+    fn next(&self, serial: u32) -> u32 {
+        match self {
+            SerialMode::Date => {
+                let today = chrono::Local::now()
+                    .format("%Y%m%d")
+                    .to_string()
+                    .parse::<u32>()
+                    .unwrap_or(0)
+                    * 100;
+                if serial < today {
+                    today
+                } else {
+                    serial.wrapping_add(1)
+                }
+            },
+            SerialMode::Increment => serial.wrapping_add(1),
+        }
+    }
+}
+
+impl std::str::FromStr for SerialMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "date" => Ok(SerialMode::Date),
+            "increment" => Ok(SerialMode::Increment),
+            _ => bail!("unsupported serial mode: {}", s),
+        }
+    }
+}
+
+/// A token of the zone file, as a byte range of a line
+#[derive(Debug, Clone, Copy)]
+struct Token {
+    line: usize,
+    start: usize,
+    end: usize,
+}
+
+#[derive(Debug)]
+struct Record {
+    /// Absolute and lowercase, with the trailing dot
+    owner: String,
+    explicit_owner: bool,
+    kind: String,
+    ttl: Option<u32>,
+    rdata: Vec<Token>,
+}
+
+/// The lines `start..end` of the file, a record spanning several lines with parentheses is one entry
+#[derive(Debug)]
+struct Entry {
+    start: usize,
+    end: usize,
+    record: Option<Record>,
+}
+
+struct Zone {
+    lines: Vec<String>,
+    entries: Vec<Entry>,
+    default_ttl: Option<u32>,
+}
+
+/// Split a line into tokens, skipping comments and tracking the parentheses
+fn scan_line(line: &str, line_index: usize, tokens: &mut Vec<Token>, depth: &mut i32) {
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b' ' | b'\t' | b'\r' => i += 1,
+            b';' => break,
+            b'(' => {
+                *depth += 1;
+                i += 1;
+            },
+            b')' => {
+                *depth -= 1;
+                i += 1;
+            },
+            b'"' => {
+                let start = i;
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                i = (i + 1).min(bytes.len());
+                tokens.push(Token {
+                    line: line_index,
+                    start,
+                    end: i,
+                });
+            },
+            _ => {
+                let start = i;
+                while i < bytes.len() && !matches!(bytes[i], b' ' | b'\t' | b'\r' | b';' | b'(' | b')' | b'"') {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                tokens.push(Token {
+                    line: line_index,
+                    start,
+                    end: i.min(bytes.len()),
+                });
+            },
+        }
+    }
+}
+
+/// Parse a TTL like `3600` or `1h30m`
+fn parse_ttl(value: &str) -> Option<u32> {
+    if let Ok(ttl) = value.parse() {
+        return Some(ttl);
+    }
+    let mut total = 0u32;
+    let mut number = None::<u32>;
+    for c in value.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            number = Some(number.unwrap_or(0).checked_mul(10)?.checked_add(digit)?);
+            continue;
+        }
+        let unit = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            'w' => 604800,
+            _ => return None,
+        };
+        total = total.checked_add(number.take()?.checked_mul(unit)?)?;
+    }
+    if number.is_some() {
+        return None;
+    }
+    Some(total)
+}
+
+fn absolute_name(name: &str, origin: &str) -> String {
+    let name = name.to_ascii_lowercase();
+    if name == "@" {
+        origin.to_owned()
+    } else if name.ends_with('.') {
+        name
+    } else {
+        format!("{}.{}", name, origin)
+    }
+}
+
+impl Zone {
+    fn parse(content: &str, origin: &str) -> Result<Self> {
+        let lines = content.lines().map(|v| v.to_owned()).collect::<Vec<_>>();
+        let text = |t: &Token| -> &str { &lines[t.line][t.start..t.end] };
+
+        let mut entries = vec![];
+        let mut origin = origin.to_owned();
+        let mut default_ttl = None;
+        let mut last_owner = None::<String>;
+        let mut i = 0;
+        while i < lines.len() {
+            let start = i;
+            let mut tokens = vec![];
+            let mut depth = 0;
+            loop {
+                scan_line(&lines[i], i, &mut tokens, &mut depth);
+                i += 1;
+                if depth <= 0 || i >= lines.len() {
+                    break;
+                }
+            }
+            if depth > 0 {
+                bail!("unbalanced parentheses from line {}", start + 1);
+            }
+            let mut entry = Entry {
+                start,
+                end: i,
+                record: None,
+            };
+            if tokens.is_empty() {
+                entries.push(entry);
+                continue;
+            }
+
+            let first = text(&tokens[0]);
+            if first.starts_with('$') {
+                let arg = tokens.get(1).map(text);
+                match first.to_ascii_uppercase().as_str() {
+                    "$ORIGIN" => {
+                        let arg = arg.ok_or_else(|| anyhow!("$ORIGIN without a name at line {}", start + 1))?;
+                        origin = absolute_name(arg, &origin);
+                    },
+                    "$TTL" => default_ttl = arg.and_then(parse_ttl),
+                    _ => debug!("ignore {} at line {}", first, start + 1),
+                }
+                entries.push(entry);
+                continue;
+            }
+
+            let explicit_owner = !lines[start].starts_with(|c: char| c.is_whitespace());
+            let mut rest = tokens.iter();
+            let owner = if explicit_owner {
+                let owner = absolute_name(text(rest.next().unwrap()), &origin);
+                last_owner = Some(owner.clone());
+                owner
+            } else {
+                last_owner
+                    .clone()
+                    .ok_or_else(|| anyhow!("record without owner at line {}", start + 1))?
+            };
+            let mut ttl = None;
+            let mut kind = None;
+            for token in rest.by_ref() {
+                let value = text(token);
+                if matches!(value.to_ascii_uppercase().as_str(), "IN" | "CH" | "HS" | "CS") {
+                    continue;
+                }
+                if let Some(value) = parse_ttl(value) {
+                    ttl = Some(value);
+                    continue;
+                }
+                kind = Some(value.to_ascii_uppercase());
+                break;
+            }
+            let kind = kind.ok_or_else(|| anyhow!("record without type at line {}", start + 1))?;
+            entry.record = Some(Record {
+                owner,
+                explicit_owner,
+                kind,
+                ttl,
+                rdata: rest.copied().collect(),
+            });
+            entries.push(entry);
+        }
+
+        Ok(Zone {
+            lines,
+            entries,
+            default_ttl,
+        })
+    }
+
+    fn text(&self, token: &Token) -> &str {
+        &self.lines[token.line][token.start..token.end]
+    }
+
+    /// The TTL of a record without one, `$TTL` or else the SOA minimum
+    fn default_ttl(&self) -> Option<u32> {
+        self.default_ttl.or_else(|| {
+            self.entries
+                .iter()
+                .filter_map(|v| v.record.as_ref())
+                .find(|v| v.kind == "SOA")
+                .and_then(|v| v.rdata.get(6))
+                .and_then(|v| parse_ttl(self.text(v)))
+        })
+    }
+
+    fn records<'a>(&'a self, name: &'a str, kind: &'a str) -> impl Iterator<Item = &'a Record> + 'a {
+        self.entries
+            .iter()
+            .filter_map(|v| v.record.as_ref())
+            .filter(move |v| v.owner == name && v.kind == kind)
+    }
+
+    /// Render the zone with the records of `kind` on `name` replaced by `ips`, and the SOA serial bumped
+    fn replace(mut self, name: &str, kind: &str, ttl: u32, ips: &[IpAddr], serial_mode: SerialMode) -> Result<String> {
+        let serial_token = self
+            .entries
+            .iter()
+            .filter_map(|v| v.record.as_ref())
+            .find(|v| v.kind == "SOA")
+            .and_then(|v| v.rdata.get(2).copied())
+            .ok_or_else(|| anyhow!("can't find the SOA serial"))?;
+        let serial = self
+            .text(&serial_token)
+            .parse::<u32>()
+            .map_err(|err| anyhow!("invalid SOA serial {}: {}", self.text(&serial_token), err))?;
+        let new_serial = serial_mode.next(serial);
+        debug!("bump SOA serial from {} to {}", serial, new_serial);
+        self.lines[serial_token.line].replace_range(serial_token.start..serial_token.end, &new_serial.to_string());
+
+        let new_lines = ips
+            .iter()
+            .map(|ip| format!("{}\t{}\tIN\t{}\t{}", name, ttl, kind, ip))
+            .collect::<Vec<_>>();
+        let mut output = vec![];
+        let mut inserted = false;
+        let mut last_owner = None::<&str>;
+        for entry in &self.entries {
+            let record = match &entry.record {
+                Some(record) => record,
+                None => {
+                    output.extend(self.lines[entry.start..entry.end].iter().cloned());
+                    continue;
+                },
+            };
+            if record.owner == name && record.kind == kind {
+                if !inserted {
+                    output.extend(new_lines.iter().cloned());
+                    inserted = true;
+                    if !ips.is_empty() {
+                        last_owner = Some(name);
+                    }
+                }
+                continue;
+            }
+            let mut lines = self.lines[entry.start..entry.end].to_vec();
+            // a record inheriting the owner of a removed one needs its own owner now
+            if !record.explicit_owner && last_owner != Some(record.owner.as_str()) {
+                lines[0] = format!("{}{}", record.owner, lines[0]);
+            }
+            last_owner = Some(&record.owner);
+            output.extend(lines);
+        }
+        if !inserted {
+            output.extend(new_lines);
+        }
+
+        let mut content = output.join("\n");
+        content.push('\n');
+        Ok(content)
+    }
+}
+
+pub struct ZoneFile {
+    path: PathBuf,
+    /// Absolute and lowercase, with the trailing dot
+    name: String,
+    origin: String,
+    serial_mode: SerialMode,
+    reload_command: Option<String>,
+}
+
+impl ZoneFile {
+    pub async fn create<P: Into<PathBuf>, D: AsRef<str>>(
+        path: P,
+        dns: D,
+        zone: Option<&str>,
+        serial_mode: SerialMode,
+        reload_command: Option<&str>,
+    ) -> Result<Self> {
+        let path = path.into();
+        let dns = dns.as_ref().trim_end_matches('.').to_ascii_lowercase();
+
+        let zone = zone_of(&dns, zone)?.to_ascii_lowercase();
+
+        let provider = ZoneFile {
+            path,
+            name: format!("{}.", dns),
+            origin: format!("{}.", zone),
+            serial_mode,
+            reload_command: reload_command.map(|v| v.to_owned()),
+        };
+        // fail early on a zone file that can't be handled
+        provider.load().await?;
+        Ok(provider)
+    }
+
+    async fn load(&self) -> Result<Zone> {
+        let content = tokio::fs::read_to_string(&self.path)
+            .await
+            .map_err(|err| anyhow!("can't read zone file {}: {}", self.path.display(), err))?;
+        Zone::parse(&content, &self.origin).map_err(|err| anyhow!("zone file {}: {}", self.path.display(), err))
+    }
+
+    async fn get_records(&self, kind: &str) -> Result<Vec<DNSRecord>> {
+        let zone = self.load().await?;
+        let mut records = vec![];
+        for record in zone.records(&self.name, kind) {
+            let value = record
+                .rdata
+                .first()
+                .map(|v| zone.text(v))
+                .ok_or_else(|| anyhow!("{} record of {} without address", kind, self.name))?;
+            records.push(DNSRecord {
+                ip: value.parse()?,
+                ttl: record
+                    .ttl
+                    .or_else(|| zone.default_ttl())
+                    .ok_or_else(|| anyhow!("can't find the ttl of {}", self.name))?,
+            });
+        }
+        Ok(records)
+    }
+
+    async fn set_records(&self, kind: &str, ttl: u32, ips: &[IpAddr]) -> Result<()> {
+        let _guard = lock_local_files().await;
+        let zone = self.load().await?;
+        let content = zone.replace(&self.name, kind, ttl, ips, self.serial_mode)?;
+        write_file_atomic(&self.path, &content).await?;
+        info!("zone file {} written", self.path.display());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Provider for ZoneFile {
+    type DNSRecord = DNSRecord;
+
+    async fn get_dns_record(&self, family: IpType) -> Result<Vec<Self::DNSRecord>> {
+        self.get_records(record_type_from_family(family)).await
+    }
+
+    async fn create_dns_record(&self, ip: &IpAddr, ttl: u32) -> Result<()> {
+        create_in_record_set(self, ip, ttl).await
+    }
+
+    async fn update_dns_record(&self, record: &Self::DNSRecord, ip: &IpAddr) -> Result<()> {
+        update_in_record_set(self, &record.ip, ip, record.ttl).await
+    }
+
+    async fn delete_dns_record(&self, record: &Self::DNSRecord) -> Result<()> {
+        delete_from_record_set(self, &record.ip, record.ttl).await
+    }
+
+    fn supports_replace(&self) -> bool {
+        true
+    }
+
+    async fn replace_dns_records(
+        &self,
+        family: IpType,
+        _records: &[Self::DNSRecord],
+        ips: &[IpAddr],
+        ttl: u32,
+    ) -> Result<()> {
+        self.set_records(record_type_from_family(family), ttl, ips).await
+    }
+
+    async fn commit_changes(&self) -> Result<()> {
+        if let Some(command) = &self.reload_command {
+            run_command(command).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZONE: &str = "$ORIGIN example.com.
+@\tIN\tSOA\tns1 hostmaster (
+\t\t2023010101 ; serial
+\t\t3600 900 604800 1h )
+\tIN\tNS\tns1
+ns1\t600\tIN\tA\t192.0.2.1
+home\tIN\tA\t203.0.113.8
+\tIN\tA\t203.0.113.9
+\tIN\tTXT\t\"v=spf1 -all\"
+";
+
+    fn addresses(zone: &Zone, name: &str, kind: &str) -> Vec<String> {
+        zone.records(name, kind)
+            .map(|v| zone.text(&v.rdata[0]).to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn ttl_units() {
+        assert_eq!(parse_ttl("3600"), Some(3600));
+        assert_eq!(parse_ttl("1h30m"), Some(5400));
+        assert_eq!(parse_ttl("1W"), Some(604800));
+        assert_eq!(parse_ttl("10"), Some(10));
+        assert_eq!(parse_ttl("1h30"), None);
+        assert_eq!(parse_ttl("A"), None);
+    }
+
+    #[test]
+    fn parse_zone() {
+        let zone = Zone::parse(ZONE, "example.com.").unwrap();
+        assert_eq!(
+            addresses(&zone, "home.example.com.", "A"),
+            ["203.0.113.8", "203.0.113.9"]
+        );
+        assert_eq!(addresses(&zone, "ns1.example.com.", "A"), ["192.0.2.1"]);
+        assert_eq!(addresses(&zone, "example.com.", "NS"), ["ns1"]);
+        assert_eq!(zone.records("home.example.com.", "TXT").count(), 1);
+        assert_eq!(zone.records("ns1.example.com.", "A").next().unwrap().ttl, Some(600));
+        assert_eq!(zone.records("home.example.com.", "A").next().unwrap().ttl, None);
+
+        let soa = zone.records("example.com.", "SOA").next().unwrap();
+        assert_eq!(soa.rdata.len(), 7);
+        assert_eq!(zone.text(&soa.rdata[2]), "2023010101");
+    }
+
+    #[test]
+    fn default_ttl() {
+        let zone = Zone::parse(ZONE, "example.com.").unwrap();
+        assert_eq!(zone.default_ttl(), Some(3600));
+
+        let zone = Zone::parse(&format!("$TTL 300\n{}", ZONE), "example.com.").unwrap();
+        assert_eq!(zone.default_ttl(), Some(300));
+    }
+
+    #[test]
+    fn parse_illegal_zone() {
+        assert!(Zone::parse("@ IN SOA ns1 hostmaster ( 1 2 3\n", "example.com.").is_err());
+        assert!(Zone::parse("\tIN A 192.0.2.1\n", "example.com.").is_err());
+        assert!(Zone::parse("home 300 IN\n", "example.com.").is_err());
+    }
+
+    #[test]
+    fn replace_records() {
+        let zone = Zone::parse(ZONE, "example.com.").unwrap();
+        let ips = ["2001:db8::1".parse().unwrap()];
+        let content = zone
+            .replace("home.example.com.", "A", 300, &ips, SerialMode::Increment)
+            .unwrap();
+        assert!(content.contains("\t\t2023010102 ; serial"));
+        assert!(content.contains("home.example.com.\t300\tIN\tA\t2001:db8::1\n"));
+        assert!(!content.contains("203.0.113"));
+
+        let zone = Zone::parse(&content, "example.com.").unwrap();
+        assert_eq!(addresses(&zone, "home.example.com.", "A"), ["2001:db8::1"]);
+        assert_eq!(addresses(&zone, "home.example.com.", "TXT"), ["\"v=spf1 -all\""]);
+        assert_eq!(addresses(&zone, "ns1.example.com.", "A"), ["192.0.2.1"]);
+    }
+
+    #[test]
+    fn remove_records() {
+        let zone = Zone::parse(ZONE, "example.com.").unwrap();
+        let content = zone
+            .replace("home.example.com.", "A", 300, &[], SerialMode::Increment)
+            .unwrap();
+        // the TXT record inherited the owner of the removed records
+        assert!(content.contains("\nhome.example.com.\tIN\tTXT"));
+        let zone = Zone::parse(&content, "example.com.").unwrap();
+        assert_eq!(zone.records("home.example.com.", "A").count(), 0);
+        assert_eq!(zone.records("home.example.com.", "TXT").count(), 1);
+    }
+
+    #[test]
+    fn replace_new_name() {
+        let zone = Zone::parse(ZONE, "example.com.").unwrap();
+        let ips = ["192.0.2.7".parse().unwrap()];
+        let content = zone
+            .replace("nas.example.com.", "A", 600, &ips, SerialMode::Increment)
+            .unwrap();
+        assert!(content.ends_with("nas.example.com.\t600\tIN\tA\t192.0.2.7\n"));
+        assert_eq!(
+            addresses(
+                &Zone::parse(&content, "example.com.").unwrap(),
+                "home.example.com.",
+                "A"
+            ),
+            ["203.0.113.8", "203.0.113.9"]
+        );
+
+        let zone = Zone::parse(&content, "example.com.").unwrap();
+        let content = zone
+            .replace("nas.example.com.", "A", 600, &[], SerialMode::Increment)
+            .unwrap();
+        assert!(!content.contains("nas.example.com."));
+        assert!(content.contains("\t\t2023010103 ; serial"));
+    }
+
+    #[test]
+    fn replace_without_soa() {
+        let zone = Zone::parse("home IN A 192.0.2.1\n", "example.com.").unwrap();
+        assert!(zone
+            .replace("home.example.com.", "A", 300, &[], SerialMode::Increment)
+            .is_err());
+    }
+
+    #[test]
+    fn next_serial() {
+        assert_eq!(SerialMode::Increment.next(1), 2);
+        assert_eq!(SerialMode::Increment.next(u32::MAX), 0);
+
+        let today = chrono::Local::now()
+            .format("%Y%m%d")
+            .to_string()
+            .parse::<u32>()
+            .unwrap()
+            * 100;
+        assert_eq!(SerialMode::Date.next(1), today);
+        assert_eq!(SerialMode::Date.next(2000010105), today);
+        assert_eq!(SerialMode::Date.next(today), today + 1);
+        assert_eq!(SerialMode::Date.next(today + 42), today + 43);
+    }
+}
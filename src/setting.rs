@@ -34,6 +34,12 @@ pub struct Provider {
     pub kind: String,
     pub force: bool,
     pub ttl: u32,
+    /// Take over the records that already exist on the name instead of leaving them alone
+    #[serde(default)]
+    pub adopt_existing: bool,
+    /// Only log the changes to the records, the notifiers are told what would change
+    #[serde(default)]
+    pub dry_run: bool,
     #[serde(flatten)]
     pub args: HashMap<String, Value>,
 }
@@ -58,6 +64,8 @@ pub struct Base {
     pub task_startup_interval: u64,
     #[serde(default = "default_task_retry_timeout")]
     pub task_retry_timeout: u64,
+    #[serde(default = "default_state_file")]
+    pub state_file: String,
 }
 
 fn default_task_startup_interval() -> u64 {
@@ -68,11 +76,16 @@ fn default_task_retry_timeout() -> u64 {
     10
 }
 
+fn default_state_file() -> String {
+    "state.json".to_owned()
+}
+
 impl Default for Base {
     fn default() -> Self {
         Self {
             task_startup_interval: default_task_startup_interval(),
             task_retry_timeout: default_task_retry_timeout(),
+            state_file: default_state_file(),
         }
     }
 }
@@ -0,0 +1,249 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io::ErrorKind;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use log::debug;
+use tokio::sync::Mutex;
+
+use crate::providers::write_file_atomic;
+use crate::IpType;
+
+/// The addresses of the records written by ddns-rs, by provider and then by family
+type Owned = BTreeMap<String, BTreeMap<String, BTreeSet<IpAddr>>>;
+
+/// The local state file, it records which records ddns-rs owns so the ones added by
+/// hand on the same name are never touched
+pub(crate) struct State {
+    path: PathBuf,
+    owned: Mutex<Owned>,
+}
+
+impl State {
+    /// A missing file is an empty state, it's created on the first change
+    pub(crate) async fn load<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        let path = path.into();
+        let owned = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|err| anyhow!("can't parse state file {}: {}", path.display(), err))?,
+            Err(err) if err.kind() == ErrorKind::NotFound => Owned::new(),
+            Err(err) => return Err(anyhow!("can't read state file {}: {}", path.display(), err)),
+        };
+        Ok(State {
+            path,
+            owned: Mutex::new(owned),
+        })
+    }
+
+    /// `None` when nothing was ever recorded for the provider and family
+    async fn owned(&self, provider: &str, family: IpType) -> Option<HashSet<IpAddr>> {
+        let owned = self.owned.lock().await;
+        owned
+            .get(provider)
+            .and_then(|v| v.get(family_key(family)))
+            .map(|v| v.iter().copied().collect())
+    }
+
+    /// Change the owned addresses of a provider and family, the file is only written when they changed
+    /// or weren't recorded yet
+    async fn modify<F>(&self, provider: &str, family: IpType, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut BTreeSet<IpAddr>),
+    {
+        let mut owned = self.owned.lock().await;
+        let recorded = owned
+            .get(provider)
+            .map(|v| v.contains_key(family_key(family)))
+            .unwrap_or(false);
+        let ips = owned
+            .entry(provider.to_owned())
+            .or_default()
+            .entry(family_key(family).to_owned())
+            .or_default();
+        let before = ips.clone();
+        f(ips);
+        if recorded && *ips == before {
+            return Ok(());
+        }
+        debug!("saving state to {}", self.path.display());
+        let content = serde_json::to_string_pretty(&*owned)?;
+        write_file_atomic(&self.path, &content).await
+    }
+}
+
+fn family_key(family: IpType) -> &'static str {
+    match family {
+        IpType::V4 => "ipv4",
+        IpType::V6 => "ipv6",
+    }
+}
+
+/// The view of the state for one provider
+pub(crate) struct Ownership {
+    state: Arc<State>,
+    provider: String,
+    /// Whether the records that already exist are taken over instead of left alone
+    adopt_existing: bool,
+    /// Nothing is written to the state file in a dry run, as no record is changed
+    dry_run: bool,
+}
+
+impl Ownership {
+    pub(crate) fn new<S: Into<String>>(state: Arc<State>, provider: S, adopt_existing: bool, dry_run: bool) -> Self {
+        Ownership {
+            state,
+            provider: provider.into(),
+            adopt_existing,
+            dry_run,
+        }
+    }
+
+    /// Whether the record of `ip` is managed by ddns-rs
+    pub(crate) fn is_managed(&self, owned: &HashSet<IpAddr>, ip: &IpAddr) -> bool {
+        self.adopt_existing || owned.contains(ip)
+    }
+
+    /// `None` before the first run with the state file
+    pub(crate) async fn owned(&self, family: IpType) -> Option<HashSet<IpAddr>> {
+        self.state.owned(&self.provider, family).await
+    }
+
+    pub(crate) async fn claim(&self, family: IpType, ip: &IpAddr) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+        self.state
+            .modify(&self.provider, family, |ips| {
+                ips.insert(*ip);
+            })
+            .await
+    }
+
+    pub(crate) async fn release(&self, family: IpType, ip: &IpAddr) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+        self.state
+            .modify(&self.provider, family, |ips| {
+                ips.remove(ip);
+            })
+            .await
+    }
+
+    /// Replace the owned addresses once the records are reconciled
+    pub(crate) async fn set(&self, family: IpType, new_ips: &[IpAddr]) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+        self.state
+            .modify(&self.provider, family, |ips| {
+                *ips = new_ips.iter().copied().collect();
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    /// A state file path of its own for each test, removed beforehand
+    pub(crate) fn state_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("ddns-rs-{}-{}.json", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn read(path: &Path) -> serde_json::Value {
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    fn ips(values: &[&str]) -> HashSet<IpAddr> {
+        values.iter().map(|v| v.parse().unwrap()).collect()
+    }
+
+    #[tokio::test]
+    async fn load_missing_file() {
+        let path = state_path("missing");
+        let state = State::load(&path).await.unwrap();
+        assert_eq!(state.owned("p1", IpType::V4).await, None);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn load_illegal_file() {
+        let path = state_path("illegal");
+        std::fs::write(&path, "{\"p1\": [").unwrap();
+        assert!(State::load(&path).await.is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn load_file() {
+        let path = state_path("load");
+        std::fs::write(&path, r#"{"p1": {"ipv4": ["192.0.2.1"], "ipv6": []}}"#).unwrap();
+        let state = State::load(&path).await.unwrap();
+        assert_eq!(state.owned("p1", IpType::V4).await, Some(ips(&["192.0.2.1"])));
+        assert_eq!(state.owned("p1", IpType::V6).await, Some(ips(&[])));
+        assert_eq!(state.owned("p2", IpType::V4).await, None);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn modify_writes_changes() {
+        let path = state_path("modify");
+        let state = State::load(&path).await.unwrap();
+
+        // recording an empty set is written too, so the next run knows it was seeded
+        state.modify("p1", IpType::V6, |_| {}).await.unwrap();
+        assert_eq!(read(&path), serde_json::json!({"p1": {"ipv6": []}}));
+
+        state
+            .modify("p1", IpType::V4, |ips| {
+                ips.insert("192.0.2.1".parse().unwrap());
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            read(&path),
+            serde_json::json!({"p1": {"ipv4": ["192.0.2.1"], "ipv6": []}})
+        );
+
+        // nothing changed, nothing written
+        std::fs::remove_file(&path).unwrap();
+        state.modify("p1", IpType::V4, |_| {}).await.unwrap();
+        assert!(!path.exists());
+
+        let state = State::load(&path).await.unwrap();
+        assert_eq!(state.owned("p1", IpType::V4).await, None);
+    }
+
+    #[tokio::test]
+    async fn ownership() {
+        let path = state_path("ownership");
+        let state = Arc::new(State::load(&path).await.unwrap());
+        let ip = "192.0.2.1".parse().unwrap();
+
+        let ownership = Ownership::new(state.clone(), "p1", false, false);
+        ownership.claim(IpType::V4, &ip).await.unwrap();
+        assert_eq!(ownership.owned(IpType::V4).await, Some(ips(&["192.0.2.1"])));
+        assert!(ownership.is_managed(&ips(&["192.0.2.1"]), &ip));
+        assert!(!ownership.is_managed(&ips(&[]), &ip));
+        ownership.release(IpType::V4, &ip).await.unwrap();
+        assert_eq!(ownership.owned(IpType::V4).await, Some(ips(&[])));
+
+        let adopting = Ownership::new(state.clone(), "p2", true, false);
+        assert!(adopting.is_managed(&ips(&[]), &ip));
+
+        let dry_run = Ownership::new(state.clone(), "p3", false, true);
+        dry_run.set(IpType::V4, &[ip]).await.unwrap();
+        assert_eq!(dry_run.owned(IpType::V4).await, None);
+
+        assert_eq!(read(&path), serde_json::json!({"p1": {"ipv4": []}}));
+        std::fs::remove_file(&path).unwrap();
+    }
+}